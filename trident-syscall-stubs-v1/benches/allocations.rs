@@ -0,0 +1,99 @@
+//! Bytes allocated by a CPI for growing instruction data, run with
+//! `cargo bench --bench allocations`.
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use std::alloc::GlobalAlloc;
+use std::alloc::Layout;
+use std::alloc::System;
+use std::cell::Cell;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+use solana_program::account_info::AccountInfo;
+use solana_program::entrypoint::ProgramResult;
+use solana_program::instruction::AccountMeta as ProgramAccountMeta;
+use solana_program::instruction::Instruction as ProgramInstruction;
+use solana_sdk::account::AccountSharedData;
+
+use common::*;
+use trident_syscall_stubs_v1::*;
+
+/// The system allocator, counting the allocated bytes.
+struct CountingAllocator;
+
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATED_BYTES.fetch_add(new_size, Ordering::Relaxed);
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+thread_local! {
+    /// Bytes and number of allocations of the last CPI of `invoke_and_count`.
+    static CPI_ALLOCATIONS: Cell<(usize, usize)> = const { Cell::new((0, 0)) };
+}
+
+/// Invokes the program of the second account with the data, counting the allocations of the CPI.
+fn invoke_and_count(
+    _program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let instruction = ProgramInstruction {
+        program_id: *accounts[1].key,
+        accounts: vec![ProgramAccountMeta::new(*accounts[0].key, false)],
+        data: data.to_vec(),
+    };
+    let bytes_before = ALLOCATED_BYTES.load(Ordering::Relaxed);
+    let allocations_before = ALLOCATIONS.load(Ordering::Relaxed);
+    solana_program::program::invoke(&instruction, accounts)?;
+    CPI_ALLOCATIONS.set((
+        ALLOCATED_BYTES.load(Ordering::Relaxed) - bytes_before,
+        ALLOCATIONS.load(Ordering::Relaxed) - allocations_before,
+    ));
+    Ok(())
+}
+
+/// Touches none of its accounts.
+fn noop(
+    _program_id: &solana_program::pubkey::Pubkey,
+    _accounts: &[AccountInfo],
+    _data: &[u8],
+) -> ProgramResult {
+    Ok(())
+}
+
+fn main() {
+    set_stubs_v1();
+    set_program_entrypoint(&CALLEE, noop);
+    let accounts = accounts(AccountSharedData::new(1, 8, &CALLEE));
+
+    for data_len in [0, 1 << 10, 4 << 10, 10 << 10] {
+        let data = vec![7; data_len];
+        let processed = process_one_instruction(&CALLER, invoke_and_count, &accounts, &data);
+        assert_eq!(processed.result, Ok(()));
+        let (bytes, allocations) = CPI_ALLOCATIONS.get();
+        println!(
+            "{data_len:>6} bytes of instruction data: {bytes:>7} bytes in {allocations} allocations"
+        );
+    }
+}
@@ -0,0 +1,93 @@
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use criterion::criterion_group;
+use criterion::criterion_main;
+use criterion::Criterion;
+
+use solana_program::account_info::AccountInfo;
+use solana_program::entrypoint::ProgramResult;
+use solana_program::instruction::AccountMeta as ProgramAccountMeta;
+use solana_program::instruction::Instruction as ProgramInstruction;
+use solana_sdk::account::AccountSharedData;
+use solana_sdk::instruction::AccountMeta;
+use solana_sdk::pubkey::Pubkey;
+
+use common::*;
+use trident_syscall_stubs_v1::*;
+
+/// Invokes the program of the last account with all the other accounts as writable accounts.
+fn invoke_with_all_accounts(
+    _program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let (program, accounts_of_callee) = accounts.split_last().unwrap();
+    let instruction = ProgramInstruction {
+        program_id: *program.key,
+        accounts: accounts_of_callee
+            .iter()
+            .map(|account| ProgramAccountMeta::new(*account.key, false))
+            .collect(),
+        data: data.to_vec(),
+    };
+    solana_program::program::invoke(&instruction, accounts)
+}
+
+/// Touches none of its accounts.
+fn noop(
+    _program_id: &solana_program::pubkey::Pubkey,
+    _accounts: &[AccountInfo],
+    _data: &[u8],
+) -> ProgramResult {
+    Ok(())
+}
+
+/// `count` writable accounts of `data_len` bytes owned by the callee, followed by the callee.
+fn accounts_of_size(count: u8, data_len: usize) -> Vec<(AccountMeta, AccountSharedData)> {
+    let mut accounts = (0..count)
+        .map(|index| {
+            (
+                AccountMeta::new(Pubkey::new_from_array([index + 10; 32]), false),
+                AccountSharedData::new(1, data_len, &CALLEE),
+            )
+        })
+        .collect::<Vec<_>>();
+    accounts.push((AccountMeta::new_readonly(CALLEE, false), program_account()));
+    accounts
+}
+
+fn cpi_with_64_accounts(c: &mut Criterion) {
+    set_stubs_v1();
+    set_program_entrypoint(&CALLEE, noop);
+    let accounts = accounts_of_size(64, 8);
+    assert_eq!(
+        process_one_instruction(&CALLER, invoke_with_all_accounts, &accounts, &[]).result,
+        Ok(())
+    );
+
+    c.bench_function("cpi_with_64_accounts", |b| {
+        b.iter(|| process_one_instruction(&CALLER, invoke_with_all_accounts, &accounts, &[]))
+    });
+}
+
+fn cpi_with_an_unchanged_large_account(c: &mut Criterion) {
+    set_stubs_v1();
+    set_program_entrypoint(&CALLEE, noop);
+    let accounts = accounts_of_size(1, 1 << 20);
+    assert_eq!(
+        process_one_instruction(&CALLER, invoke_with_all_accounts, &accounts, &[]).result,
+        Ok(())
+    );
+
+    c.bench_function("cpi_with_an_unchanged_large_account", |b| {
+        b.iter(|| process_one_instruction(&CALLER, invoke_with_all_accounts, &accounts, &[]))
+    });
+}
+
+criterion_group!(
+    benches,
+    cpi_with_64_accounts,
+    cpi_with_an_unchanged_large_account
+);
+criterion_main!(benches);
@@ -0,0 +1,133 @@
+use std::mem::size_of;
+
+use solana_sdk::account::AccountSharedData;
+use solana_sdk::account::ReadableAccount;
+use solana_sdk::account::WritableAccount;
+use solana_sdk::instruction::AccountMeta;
+use solana_sdk::pubkey::Pubkey;
+
+use solana_program::account_info::AccountInfo;
+use solana_program::entrypoint::MAX_PERMITTED_DATA_INCREASE;
+
+/// Fields of an account laid out as in the serialized input of a program, AccountInfo reads
+/// the original data length from the 4 bytes in front of the key.
+#[repr(C)]
+struct StoredAccount {
+    _padding: [u8; 4],
+    original_data_len: u32,
+    key: solana_program::pubkey::Pubkey,
+    owner: solana_program::pubkey::Pubkey,
+    lamports: u64,
+    is_signer: bool,
+    is_writable: bool,
+    executable: bool,
+    rent_epoch: u64,
+    /// The data length followed by the data and the space a realloc may grow it into.
+    data: Vec<u64>,
+}
+
+/// Backing storage of the `AccountInfo`s of owned accounts, e.g. to pass them to
+/// `sol_invoke_signed` from harness code. The account infos support `realloc` as those of a
+/// program do, and the changes made through them can be read back with `accounts`.
+pub struct AccountInfoStorage {
+    accounts: Vec<StoredAccount>,
+}
+
+impl AccountInfoStorage {
+    /// Store the accounts, duplicate accounts are stored once with their privileges merged.
+    pub fn new(accounts: &[(AccountMeta, AccountSharedData)]) -> Self {
+        let mut stored_accounts: Vec<StoredAccount> = Vec::with_capacity(accounts.len());
+        for (account_meta, account) in accounts {
+            let key =
+                solana_program::pubkey::Pubkey::new_from_array(account_meta.pubkey.to_bytes());
+            if let Some(stored_account) = stored_accounts
+                .iter_mut()
+                .find(|stored_account| stored_account.key == key)
+            {
+                stored_account.is_signer |= account_meta.is_signer;
+                stored_account.is_writable |= account_meta.is_writable;
+                continue;
+            }
+
+            let data_len = account.data().len();
+            let mut data =
+                vec![0u64; 1 + (data_len + MAX_PERMITTED_DATA_INCREASE).div_ceil(size_of::<u64>())];
+            data[0] = data_len as u64;
+            let bytes = unsafe {
+                std::slice::from_raw_parts_mut(data[1..].as_mut_ptr() as *mut u8, data_len)
+            };
+            bytes.copy_from_slice(account.data());
+
+            #[allow(deprecated)]
+            let executable = account.executable();
+            stored_accounts.push(StoredAccount {
+                _padding: [0; 4],
+                original_data_len: data_len as u32,
+                key,
+                owner: solana_program::pubkey::Pubkey::new_from_array(account.owner().to_bytes()),
+                lamports: account.lamports(),
+                is_signer: account_meta.is_signer,
+                is_writable: account_meta.is_writable,
+                executable,
+                rent_epoch: account.rent_epoch(),
+                data,
+            });
+        }
+        Self {
+            accounts: stored_accounts,
+        }
+    }
+
+    /// Account infos of the stored accounts, in the order they were first given.
+    pub fn account_infos(&mut self) -> Vec<AccountInfo<'_>> {
+        self.accounts
+            .iter_mut()
+            .map(|stored_account| {
+                let data_len = stored_account.data[0] as usize;
+                let data = unsafe {
+                    std::slice::from_raw_parts_mut(
+                        stored_account.data[1..].as_mut_ptr() as *mut u8,
+                        data_len,
+                    )
+                };
+                AccountInfo::new(
+                    &stored_account.key,
+                    stored_account.is_signer,
+                    stored_account.is_writable,
+                    &mut stored_account.lamports,
+                    data,
+                    &stored_account.owner,
+                    stored_account.executable,
+                    stored_account.rent_epoch,
+                )
+            })
+            .collect()
+    }
+
+    /// The stored accounts with the changes made through their account infos.
+    pub fn accounts(&self) -> Vec<(Pubkey, AccountSharedData)> {
+        self.accounts
+            .iter()
+            .map(|stored_account| {
+                let data_len = stored_account.data[0] as usize;
+                let data = unsafe {
+                    std::slice::from_raw_parts(
+                        stored_account.data[1..].as_ptr() as *const u8,
+                        data_len,
+                    )
+                };
+                let account = AccountSharedData::create(
+                    stored_account.lamports,
+                    data.to_vec(),
+                    Pubkey::new_from_array(stored_account.owner.to_bytes()),
+                    stored_account.executable,
+                    stored_account.rent_epoch,
+                );
+                (
+                    Pubkey::new_from_array(stored_account.key.to_bytes()),
+                    account,
+                )
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,278 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::transaction_context::IndexOfAccount;
+use solana_sdk::transaction_context::InstructionAccount;
+
+use solana_program::program_error::ProgramError;
+
+use solana_compute_budget::compute_budget::ComputeBudget;
+
+thread_local! {
+    static STUB_CONFIG: RefCell<StubConfig> = RefCell::new(StubConfig::default());
+}
+
+/// Called by `sol_invoke_signed` with the instruction of the CPI and the instruction accounts
+/// and program indices `prepare_instruction` resolved it to.
+pub type PrepareInstructionHook =
+    Rc<dyn Fn(&Instruction, &[InstructionAccount], &[IndexOfAccount])>;
+
+/// Called by `sol_invoke_signed` before the CPI is made, with the program id and the stack height
+/// of the callee, 2 for a CPI of the top level instruction, and the instruction. An error aborts
+/// the CPI and is returned to the caller.
+pub type CpiEnterHook = Rc<dyn Fn(&Pubkey, usize, &Instruction) -> Result<(), ProgramError>>;
+
+/// Called by `sol_set_return_data` with the program setting the return data and the data,
+/// which it may rewrite before it is stored.
+pub type ReturnDataHook = Rc<dyn Fn(&Pubkey, &mut Vec<u8>)>;
+
+/// Configuration of the stubs installed by `set_stubs_v1_with_config`. Like the rest of the
+/// stub state it applies to the thread it was installed on, the default matches `set_stubs_v1`.
+#[derive(Clone)]
+pub struct StubConfig {
+    capture_logs: bool,
+    fault_injection: bool,
+    compute_budget: ComputeBudget,
+    charge_compute_units: bool,
+    max_cpi_depth: Option<usize>,
+    syscall_metrics: bool,
+    cpi_compute_metrics: bool,
+    no_panic: bool,
+    prepare_instruction_hook: Option<PrepareInstructionHook>,
+    max_accounts_data_growth: Option<u64>,
+    executable_checks: bool,
+    return_data_hook: Option<ReturnDataHook>,
+    strict_memory: bool,
+    log_ring_buffer: Option<usize>,
+    cpi_enter_hook: Option<CpiEnterHook>,
+}
+
+impl Default for StubConfig {
+    fn default() -> Self {
+        Self {
+            capture_logs: true,
+            fault_injection: false,
+            compute_budget: ComputeBudget::default(),
+            charge_compute_units: false,
+            max_cpi_depth: None,
+            syscall_metrics: false,
+            cpi_compute_metrics: false,
+            no_panic: false,
+            prepare_instruction_hook: None,
+            max_accounts_data_growth: None,
+            executable_checks: false,
+            return_data_hook: None,
+            strict_memory: false,
+            log_ring_buffer: None,
+            cpi_enter_hook: None,
+        }
+    }
+}
+
+impl fmt::Debug for StubConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StubConfig")
+            .field("capture_logs", &self.capture_logs)
+            .field("fault_injection", &self.fault_injection)
+            .field("compute_budget", &self.compute_budget)
+            .field("charge_compute_units", &self.charge_compute_units)
+            .field("max_cpi_depth", &self.max_cpi_depth)
+            .field("syscall_metrics", &self.syscall_metrics)
+            .field("cpi_compute_metrics", &self.cpi_compute_metrics)
+            .field("no_panic", &self.no_panic)
+            .field(
+                "prepare_instruction_hook",
+                &self.prepare_instruction_hook.is_some(),
+            )
+            .field("max_accounts_data_growth", &self.max_accounts_data_growth)
+            .field("executable_checks", &self.executable_checks)
+            .field("return_data_hook", &self.return_data_hook.is_some())
+            .field("strict_memory", &self.strict_memory)
+            .field("log_ring_buffer", &self.log_ring_buffer)
+            .field("cpi_enter_hook", &self.cpi_enter_hook.is_some())
+            .finish()
+    }
+}
+
+impl StubConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the messages logged by programs through `sol_log` and `sol_log_data` in the log
+    /// collector of the invoke context.
+    pub fn capture_logs(mut self, capture_logs: bool) -> Self {
+        self.capture_logs = capture_logs;
+        self
+    }
+
+    /// Fail the syscalls with the faults injected through `inject_fault`.
+    pub fn fault_injection(mut self, fault_injection: bool) -> Self {
+        self.fault_injection = fault_injection;
+        self
+    }
+
+    /// Compute budget of the instructions run by `process_one_instruction`.
+    pub fn compute_budget(mut self, compute_budget: ComputeBudget) -> Self {
+        self.compute_budget = compute_budget;
+        self
+    }
+
+    /// Charge the compute units the runtime charges for the syscalls and for each CPI, its
+    /// invocation and the instruction and account data it passes. Only the programs run by the
+    /// invoke context are charged otherwise.
+    pub fn charge_compute_units(mut self, charge_compute_units: bool) -> Self {
+        self.charge_compute_units = charge_compute_units;
+        self
+    }
+
+    /// Number of nested CPIs after which `sol_invoke_signed` fails with
+    /// `InstructionError::CallDepth`, on top of the stack height limit of the runtime.
+    pub fn max_cpi_depth(mut self, max_cpi_depth: usize) -> Self {
+        self.max_cpi_depth = Some(max_cpi_depth);
+        self
+    }
+
+    /// Count the calls of each syscall on the thread of the stubs, see `syscall_counts`.
+    pub fn syscall_metrics(mut self, syscall_metrics: bool) -> Self {
+        self.syscall_metrics = syscall_metrics;
+        self
+    }
+
+    /// Sum the compute units consumed by the CPIs at each stack height, see
+    /// `cpi_compute_units`.
+    pub fn cpi_compute_metrics(mut self, cpi_compute_metrics: bool) -> Self {
+        self.cpi_compute_metrics = cpi_compute_metrics;
+        self
+    }
+
+    /// Return the failures of the stubs which are not errors of the program as `ProgramError`s
+    /// instead of panicking, and fail the instruction of a program aborted by a syscall on chain,
+    /// e.g. by setting return data longer than `MAX_RETURN_DATA`, with `InvalidArgument`. Borrow
+    /// conflicts of account infos always fail the CPI with `AccountBorrowFailed`.
+    pub fn no_panic(mut self, no_panic: bool) -> Self {
+        self.no_panic = no_panic;
+        self
+    }
+
+    /// Observe how the account metas of each CPI are resolved, see `PrepareInstructionHook`.
+    pub fn prepare_instruction_hook(mut self, hook: PrepareInstructionHook) -> Self {
+        self.prepare_instruction_hook = Some(hook);
+        self
+    }
+
+    /// Number of bytes the account data of a transaction may grow by in total, across its
+    /// instructions and their CPIs, after which a resize fails with
+    /// `InstructionError::MaxAccountsDataAllocationsExceeded`, on top of the limit of the runtime.
+    pub fn max_accounts_data_growth(mut self, max_accounts_data_growth: u64) -> Self {
+        self.max_accounts_data_growth = Some(max_accounts_data_growth);
+        self
+    }
+
+    /// Reject changes of the data and the lamports of executable accounts with
+    /// `ExecutableDataModified` and `ExecutableLamportChange`, as the runtime does until the
+    /// removal of the executable flag checks. The transaction contexts built by
+    /// `TransactionContextBuilder` skip these checks otherwise.
+    pub fn executable_checks(mut self, executable_checks: bool) -> Self {
+        self.executable_checks = executable_checks;
+        self
+    }
+
+    /// Rewrite the return data set by programs, e.g. to test how callers handle unexpected
+    /// values, see `ReturnDataHook`.
+    pub fn return_data_hook(mut self, hook: ReturnDataHook) -> Self {
+        self.return_data_hook = Some(hook);
+        self
+    }
+
+    /// Panic in the memory syscalls when a range starting in the data of an account of a running
+    /// program runs past the data and the space it may grow into, as an access violation aborts
+    /// the program on chain.
+    pub fn strict_memory(mut self, strict_memory: bool) -> Self {
+        self.strict_memory = strict_memory;
+        self
+    }
+
+    /// Capture the messages of `sol_log` and `sol_log_data` into a ring buffer keeping only the
+    /// last `capacity` lines instead of the log collector, so that long runs do not accumulate
+    /// their logs, see `buffered_logs`. The invoke framing and the messages of builtins are logged
+    /// by the runtime into the log collector of the invoke context and are not buffered, so
+    /// `parse_invoke_framing` is not supported on the buffered lines.
+    pub fn log_ring_buffer(mut self, capacity: usize) -> Self {
+        self.log_ring_buffer = Some(capacity);
+        self
+    }
+
+    /// Instrument or veto the CPIs made by programs, see `CpiEnterHook`.
+    pub fn cpi_enter_hook(mut self, hook: CpiEnterHook) -> Self {
+        self.cpi_enter_hook = Some(hook);
+        self
+    }
+}
+
+pub(crate) fn set_stub_config(config: StubConfig) {
+    STUB_CONFIG.with(|stub_config| stub_config.replace(config));
+}
+
+pub(crate) fn capture_logs_enabled() -> bool {
+    STUB_CONFIG.with(|stub_config| stub_config.borrow().capture_logs)
+}
+
+pub(crate) fn fault_injection_enabled() -> bool {
+    STUB_CONFIG.with(|stub_config| stub_config.borrow().fault_injection)
+}
+
+pub(crate) fn configured_compute_budget() -> ComputeBudget {
+    STUB_CONFIG.with(|stub_config| stub_config.borrow().compute_budget)
+}
+
+pub(crate) fn charge_compute_units_enabled() -> bool {
+    STUB_CONFIG.with(|stub_config| stub_config.borrow().charge_compute_units)
+}
+
+pub(crate) fn configured_max_cpi_depth() -> Option<usize> {
+    STUB_CONFIG.with(|stub_config| stub_config.borrow().max_cpi_depth)
+}
+
+pub(crate) fn syscall_metrics_enabled() -> bool {
+    STUB_CONFIG.with(|stub_config| stub_config.borrow().syscall_metrics)
+}
+
+pub(crate) fn cpi_compute_metrics_enabled() -> bool {
+    STUB_CONFIG.with(|stub_config| stub_config.borrow().cpi_compute_metrics)
+}
+
+pub(crate) fn no_panic_enabled() -> bool {
+    STUB_CONFIG.with(|stub_config| stub_config.borrow().no_panic)
+}
+
+pub(crate) fn configured_prepare_instruction_hook() -> Option<PrepareInstructionHook> {
+    STUB_CONFIG.with(|stub_config| stub_config.borrow().prepare_instruction_hook.clone())
+}
+
+pub(crate) fn configured_max_accounts_data_growth() -> Option<u64> {
+    STUB_CONFIG.with(|stub_config| stub_config.borrow().max_accounts_data_growth)
+}
+
+pub(crate) fn executable_checks_enabled() -> bool {
+    STUB_CONFIG.with(|stub_config| stub_config.borrow().executable_checks)
+}
+
+pub(crate) fn configured_return_data_hook() -> Option<ReturnDataHook> {
+    STUB_CONFIG.with(|stub_config| stub_config.borrow().return_data_hook.clone())
+}
+
+pub(crate) fn strict_memory_enabled() -> bool {
+    STUB_CONFIG.with(|stub_config| stub_config.borrow().strict_memory)
+}
+
+pub(crate) fn configured_log_ring_buffer() -> Option<usize> {
+    STUB_CONFIG.with(|stub_config| stub_config.borrow().log_ring_buffer)
+}
+
+pub(crate) fn configured_cpi_enter_hook() -> Option<CpiEnterHook> {
+    STUB_CONFIG.with(|stub_config| stub_config.borrow().cpi_enter_hook.clone())
+}
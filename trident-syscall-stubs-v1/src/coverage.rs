@@ -0,0 +1,30 @@
+use std::cell::RefCell;
+use std::hash::DefaultHasher;
+use std::hash::Hasher;
+
+/// Callback receiving the hash of every message logged through `sol_log` or `sol_log_data`.
+pub type LogCoverageCallback = Box<dyn FnMut(u64)>;
+
+thread_local! {
+    static LOG_COVERAGE_CALLBACK: RefCell<Option<LogCoverageCallback>> = RefCell::new(None);
+}
+
+/// Feed the hash of each message logged on this thread to the callback, so that diverging logs
+/// can be used as coverage by the fuzzer. `None` removes the callback.
+pub fn set_log_coverage_callback(callback: Option<LogCoverageCallback>) {
+    LOG_COVERAGE_CALLBACK.with(|log_coverage_callback| log_coverage_callback.replace(callback));
+}
+
+/// The message is only hashed when a callback is set, hashing itself does not allocate.
+pub(crate) fn feed_log_coverage(hash: impl FnOnce(&mut DefaultHasher)) {
+    LOG_COVERAGE_CALLBACK.with(|log_coverage_callback| {
+        // Taken out for the call, so that the callback may replace itself
+        let Some(mut callback) = log_coverage_callback.take() else {
+            return;
+        };
+        let mut hasher = DefaultHasher::new();
+        hash(&mut hasher);
+        callback(hasher.finish());
+        log_coverage_callback.borrow_mut().get_or_insert(callback);
+    });
+}
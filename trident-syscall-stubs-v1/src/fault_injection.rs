@@ -0,0 +1,89 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use solana_sdk::instruction::InstructionError;
+
+use solana_program::program_error::ProgramError;
+
+use crate::fault_injection_enabled;
+use crate::to_program_error;
+
+/// Failure reported by a syscall instead of executing it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InjectedFault {
+    /// Return code of the syscall, sol_invoke_signed reports it as the corresponding
+    /// `ProgramError`.
+    ReturnCode(u64),
+    /// Error of the syscall, sysvar syscalls report it as the corresponding return code.
+    InstructionError(InstructionError),
+}
+
+impl InjectedFault {
+    pub(crate) fn into_return_code(self) -> u64 {
+        match self {
+            Self::ReturnCode(code) => code,
+            Self::InstructionError(err) => to_program_error(err).into(),
+        }
+    }
+
+    pub(crate) fn into_program_error(self) -> ProgramError {
+        match self {
+            Self::ReturnCode(code) => code.into(),
+            Self::InstructionError(err) => to_program_error(err),
+        }
+    }
+}
+
+struct FaultEntry {
+    fault: InjectedFault,
+    once: bool,
+}
+
+thread_local! {
+    static INJECTED_FAULTS: RefCell<HashMap<String, FaultEntry>> = RefCell::new(HashMap::new());
+}
+
+/// Make every call of the syscall with the given name (e.g. "sol_get_clock_sysvar") fail with
+/// the fault, until the fault is removed. Faults are only injected with
+/// `StubConfig::fault_injection` set.
+pub fn inject_fault(syscall: &str, fault: InjectedFault) {
+    INJECTED_FAULTS.with(|faults| {
+        faults
+            .borrow_mut()
+            .insert(syscall.to_string(), FaultEntry { fault, once: false });
+    });
+}
+
+/// Make only the next call of the syscall with the given name fail with the fault.
+pub fn inject_fault_once(syscall: &str, fault: InjectedFault) {
+    INJECTED_FAULTS.with(|faults| {
+        faults
+            .borrow_mut()
+            .insert(syscall.to_string(), FaultEntry { fault, once: true });
+    });
+}
+
+pub fn remove_fault(syscall: &str) {
+    INJECTED_FAULTS.with(|faults| {
+        faults.borrow_mut().remove(syscall);
+    });
+}
+
+pub fn clear_faults() {
+    INJECTED_FAULTS.with(|faults| faults.borrow_mut().clear());
+}
+
+pub(crate) fn take_fault(syscall: &str) -> Option<InjectedFault> {
+    if !fault_injection_enabled() {
+        return None;
+    }
+    INJECTED_FAULTS.with(|faults| {
+        let mut faults = faults.borrow_mut();
+        let entry = faults.get(syscall)?;
+        if entry.once {
+            faults.remove(syscall).map(|entry| entry.fault)
+        } else {
+            Some(entry.fault.clone())
+        }
+    })
+}
@@ -1,22 +1,603 @@
 #![allow(clippy::not_unsafe_ptr_arg_deref)]
+use std::mem::size_of;
 use std::mem::transmute;
 
+use std::cell::Cell;
 use std::cell::RefCell;
+use std::rc::Rc;
+
+use solana_sdk::account::AccountSharedData;
+use solana_sdk::account::ReadableAccount;
+use solana_sdk::instruction::AccountMeta;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::instruction::InstructionError;
+use solana_sdk::instruction::TRANSACTION_LEVEL_STACK_HEIGHT;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::stable_layout::stable_instruction::StableInstruction;
+use solana_sdk::transaction_context::IndexOfAccount;
+use solana_sdk::transaction_context::InstructionAccount;
+use solana_sdk::transaction_context::TransactionContext;
+
+use solana_program::program_error::ProgramError;
+
+use solana_log_collector::LogCollector;
+use solana_program_runtime::solana_sbpf::vm::ContextObject;
+use solana_timings::ExecuteTimings;
 
 pub use solana_program_runtime::invoke_context::InvokeContext;
 
+use crate::configured_max_accounts_data_growth;
+use crate::to_program_error;
+
 thread_local! {
-    static INVOKE_CONTEXT: RefCell<Option<usize>> = const { RefCell::new(None) };
+    static INVOKE_CONTEXT: Cell<Option<InstalledContext>> = const { Cell::new(None) };
+    static LOG_COLLECTOR: RefCell<Option<Rc<RefCell<LogCollector>>>> = const { RefCell::new(None) };
+    static RNG_STATE: Cell<u64> = const { Cell::new(0) };
+}
+
+/// The invoke context installed for the calling thread and the compute units seeded into it by
+/// `set_compute_budget`, which are kept as long as the context stays installed.
+#[derive(Clone, Copy)]
+pub(crate) struct InstalledContext {
+    ptr: usize,
+    seeded_compute_units: Option<u64>,
 }
+
+/// Install the context at `ptr`, a context installed again keeps its seeded compute units while
+/// any other context starts with its own compute unit limit.
+fn install_invoke_context(ptr: usize) -> Option<InstalledContext> {
+    INVOKE_CONTEXT.with(|invoke_context| {
+        let previous = invoke_context.get();
+        let seeded_compute_units = previous
+            .filter(|installed| installed.ptr == ptr)
+            .and_then(|installed| installed.seeded_compute_units);
+        invoke_context.set(Some(InstalledContext {
+            ptr,
+            seeded_compute_units,
+        }));
+        previous
+    })
+}
+
 pub fn set_invoke_context(new: &mut InvokeContext) {
-    INVOKE_CONTEXT.with(|invoke_context| unsafe {
-        invoke_context.replace(Some(transmute::<&mut InvokeContext, usize>(new)))
-    });
+    let ptr = unsafe { transmute::<&mut InvokeContext, usize>(new) };
+    install_invoke_context(ptr);
+}
+pub(crate) fn replace_invoke_context(
+    installed: Option<InstalledContext>,
+) -> Option<InstalledContext> {
+    INVOKE_CONTEXT.with(|invoke_context| invoke_context.replace(installed))
+}
+
+/// Restores the invoke context that was installed before `set_thread_context` when dropped.
+#[must_use = "the invoke context is uninstalled as soon as the guard is dropped"]
+pub struct InvokeContextGuard {
+    previous: Option<InstalledContext>,
+}
+
+impl Drop for InvokeContextGuard {
+    fn drop(&mut self) {
+        replace_invoke_context(self.previous.take());
+    }
+}
+
+/// Install the invoke context for the calling thread until the returned guard is dropped, also
+/// on a panic, so that a thread reused from a pool does not keep a dangling context:
+///
+/// ```ignore
+/// let _guard = set_thread_context(&mut invoke_context);
+/// invoke_context.process_instruction(...);
+/// ```
+///
+/// The guard does not borrow the context, it has to be dropped before the context is.
+pub fn set_thread_context(new: &mut InvokeContext) -> InvokeContextGuard {
+    let ptr = unsafe { transmute::<&mut InvokeContext, usize>(new) };
+    let previous = install_invoke_context(ptr);
+    InvokeContextGuard { previous }
+}
+
+/// Install the invoke context for the duration of the closure, which is handed the context, and
+/// restore the previously installed one afterwards, also on a panic. Contexts can be nested,
+/// e.g. to run a separate transaction in the middle of an instruction.
+pub fn with_invoke_context<'a, R>(
+    new: &mut InvokeContext<'a>,
+    f: impl FnOnce(&mut InvokeContext<'a>) -> R,
+) -> R {
+    let _guard = set_thread_context(new);
+    f(new)
+}
+
+pub(crate) fn is_invoke_context_set() -> bool {
+    INVOKE_CONTEXT.with(|invoke_context| invoke_context.get().is_some())
 }
 pub fn get_invoke_context<'a, 'b>() -> &'a mut InvokeContext<'b> {
-    let ptr = INVOKE_CONTEXT.with(|invoke_context| match *invoke_context.borrow() {
-        Some(val) => val,
+    let ptr = INVOKE_CONTEXT.with(|invoke_context| match invoke_context.get() {
+        Some(installed) => installed.ptr,
         None => panic!("Invoke context not set!"),
     });
     unsafe { transmute::<usize, &mut InvokeContext>(ptr) }
 }
+
+/// Send the messages of `sol_log` and `sol_log_data` on this thread to the given log collector
+/// instead of the one of the invoke context, `None` restores the log collector of the context.
+/// The invoke framing and the messages of builtins are still logged by the runtime into the log
+/// collector of the invoke context, `parse_invoke_framing` is not supported on either of them.
+pub fn set_log_collector(log_collector: Option<Rc<RefCell<LogCollector>>>) {
+    LOG_COLLECTOR.with(|custom_log_collector| custom_log_collector.replace(log_collector));
+}
+
+/// Log collector used by the log syscalls, the custom one if set.
+pub(crate) fn get_log_collector() -> Option<Rc<RefCell<LogCollector>>> {
+    LOG_COLLECTOR
+        .with(|custom_log_collector| custom_log_collector.borrow().clone())
+        .or_else(|| get_invoke_context().get_log_collector())
+}
+
+/// Drain the messages collected by the log collector of the log syscalls, so they can be
+/// inspected once the instruction has been processed.
+pub fn take_logs() -> Vec<String> {
+    get_log_collector()
+        .map(|log_collector| std::mem::take(&mut log_collector.borrow_mut().messages))
+        .unwrap_or_default()
+}
+
+/// Seed the compute meter of the current invoke context with the given amount of compute units,
+/// so that programs can be exercised close to the exhaustion of their budget.
+/// The seeded units replace the compute unit limit of the context for `consumed_compute_units`
+/// while the context stays installed, also when it is installed again.
+pub fn set_compute_budget(units: u64) {
+    get_invoke_context().mock_set_remaining(units);
+    INVOKE_CONTEXT.with(|invoke_context| {
+        invoke_context.set(invoke_context.get().map(|installed| InstalledContext {
+            seeded_compute_units: Some(units),
+            ..installed
+        }))
+    });
+}
+
+/// Compute units left in the meter of the current invoke context, as seen by
+/// `sol_remaining_compute_units`.
+pub fn remaining_compute_units() -> u64 {
+    get_invoke_context().get_remaining()
+}
+
+/// Compute units consumed from the compute unit limit of the current invoke context, or from
+/// the units seeded by `set_compute_budget`.
+pub fn consumed_compute_units() -> u64 {
+    let invoke_context = get_invoke_context();
+    let budget = INVOKE_CONTEXT
+        .with(Cell::get)
+        .and_then(|installed| installed.seeded_compute_units)
+        .unwrap_or(invoke_context.get_compute_budget().compute_unit_limit);
+    budget.saturating_sub(invoke_context.get_remaining())
+}
+
+/// Number of bytes the account data of the current transaction grew by, negative if it shrank.
+pub fn accounts_data_growth() -> i64 {
+    get_invoke_context()
+        .transaction_context
+        .accounts_resize_delta()
+        .unwrap_or_default()
+}
+
+/// Fails with `MaxAccountsDataAllocationsExceeded` if resizing account data of `old_len` bytes
+/// to `new_len` grows the account data of the transaction beyond
+/// `StubConfig::max_accounts_data_growth`, as the runtime does for its own limit.
+pub(crate) fn check_accounts_data_growth(
+    transaction_context: &TransactionContext,
+    old_len: usize,
+    new_len: usize,
+) -> Result<(), InstructionError> {
+    let Some(max_accounts_data_growth) = configured_max_accounts_data_growth() else {
+        return Ok(());
+    };
+    let length_delta = (new_len as i64).saturating_sub(old_len as i64);
+    if transaction_context
+        .accounts_resize_delta()?
+        .saturating_add(length_delta)
+        > max_accounts_data_growth.try_into().unwrap_or(i64::MAX)
+    {
+        return Err(InstructionError::MaxAccountsDataAllocationsExceeded);
+    }
+    Ok(())
+}
+
+/// Program id of the instruction currently being processed, `None` outside of an instruction.
+pub fn current_program_id() -> Option<Pubkey> {
+    let transaction_context = &get_invoke_context().transaction_context;
+    transaction_context
+        .get_current_instruction_context()
+        .and_then(|instruction_context| {
+            instruction_context.get_last_program_key(transaction_context)
+        })
+        .ok()
+        .copied()
+}
+
+/// Stack height of the instruction currently being processed, `None` outside of an instruction.
+pub fn current_stack_height() -> Option<usize> {
+    match get_invoke_context()
+        .transaction_context
+        .get_instruction_context_stack_height()
+    {
+        0 => None,
+        stack_height => Some(stack_height),
+    }
+}
+
+/// Whether the instruction currently being processed is an instruction of the transaction and
+/// not a CPI, as a program sees it by comparing `get_stack_height()` with
+/// `TRANSACTION_LEVEL_STACK_HEIGHT`. `false` outside of an instruction.
+pub fn is_top_level_instruction() -> bool {
+    current_stack_height() == Some(TRANSACTION_LEVEL_STACK_HEIGHT)
+}
+
+/// Return data of the current transaction context and the program which set it, `None` if
+/// there is none, in the same way as `sol_get_return_data` reports it.
+pub fn get_return_data() -> Option<(Pubkey, Vec<u8>)> {
+    let (program_id, data) = get_invoke_context().transaction_context.get_return_data();
+    if data.is_empty() {
+        return None;
+    }
+    Some((*program_id, data.to_vec()))
+}
+
+/// Clear the return data of the current transaction context, as the runtime does before every
+/// program runs, e.g. between CPIs made by the harness.
+pub fn clear_return_data() {
+    // Empty return data is always within MAX_RETURN_DATA
+    get_invoke_context()
+        .transaction_context
+        .set_return_data(Pubkey::default(), Vec::new())
+        .unwrap();
+}
+
+/// Seed the deterministic generator behind `random_u64` and `fill_random` of this thread, e.g.
+/// with the seed of the fuzz iteration, so that the values derived from it can be reproduced.
+pub fn seed_rng(seed: u64) {
+    RNG_STATE.with(|state| state.set(seed));
+}
+
+/// Next value of the SplitMix64 generator seeded by `seed_rng`, the same seed yields the same
+/// sequence on every platform. It is not suitable for anything but testing.
+pub fn random_u64() -> u64 {
+    RNG_STATE.with(|state| {
+        let next_state = state.get().wrapping_add(0x9E37_79B9_7F4A_7C15);
+        state.set(next_state);
+        let mut value = next_state;
+        value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        value ^ (value >> 31)
+    })
+}
+
+/// Fill the bytes with values of `random_u64`.
+pub fn fill_random(bytes: &mut [u8]) {
+    for chunk in bytes.chunks_mut(size_of::<u64>()) {
+        chunk.copy_from_slice(&random_u64().to_le_bytes()[..chunk.len()]);
+    }
+}
+
+/// Instructions processed before the current one at its stack height since its caller started,
+/// or since the start of the transaction at the top level, the most recent first. This is the
+/// trace `sol_get_processed_sibling_instruction` reads, its index is the index in the vector.
+pub fn processed_sibling_instructions() -> Vec<Instruction> {
+    let invoke_context = get_invoke_context();
+    let stack_height = invoke_context.get_stack_height();
+    let transaction_context = &invoke_context.transaction_context;
+
+    let mut sibling_instructions = Vec::new();
+    // The last instruction at the stack height is the current one, which is not its own sibling
+    let mut is_current = true;
+    for index_in_trace in (0..transaction_context.get_instruction_trace_length()).rev() {
+        let instruction_context = transaction_context
+            .get_instruction_context_at_index_in_trace(index_in_trace)
+            .unwrap_or_else(|err| panic!("{}", err));
+        if instruction_context.get_stack_height() < stack_height {
+            break;
+        }
+        if instruction_context.get_stack_height() != stack_height {
+            continue;
+        }
+        if is_current {
+            is_current = false;
+            continue;
+        }
+
+        let accounts = (0..instruction_context.get_number_of_instruction_accounts())
+            .map(|index_in_instruction| {
+                let index_in_transaction = instruction_context
+                    .get_index_of_instruction_account_in_transaction(index_in_instruction)?;
+                Ok(AccountMeta {
+                    pubkey: *transaction_context
+                        .get_key_of_account_at_index(index_in_transaction)?,
+                    is_signer: instruction_context
+                        .is_instruction_account_signer(index_in_instruction)?,
+                    is_writable: instruction_context
+                        .is_instruction_account_writable(index_in_instruction)?,
+                })
+            })
+            .collect::<Result<Vec<_>, InstructionError>>()
+            .unwrap_or_else(|err| panic!("{}", err));
+        let program_id = instruction_context
+            .get_last_program_key(transaction_context)
+            .unwrap_or_else(|err| panic!("{}", err));
+        sibling_instructions.push(Instruction {
+            program_id: *program_id,
+            accounts,
+            data: instruction_context.get_instruction_data().to_vec(),
+        });
+    }
+    sibling_instructions
+}
+
+/// Accounts and return data of a transaction context, captured by `snapshot`. The account data
+/// is reference counted, so cloning and restoring a snapshot does not copy it.
+#[derive(Clone, Debug)]
+pub struct InvokeContextSnapshot {
+    accounts: Vec<AccountSharedData>,
+    return_data: (Pubkey, Vec<u8>),
+}
+
+/// Capture the accounts and return data of the current invoke context.
+pub fn snapshot() -> InvokeContextSnapshot {
+    let transaction_context = &get_invoke_context().transaction_context;
+    let accounts = (0..transaction_context.get_number_of_accounts())
+        .map(|index| {
+            transaction_context
+                .get_account_at_index(index)
+                .unwrap()
+                .borrow()
+                .clone()
+        })
+        .collect();
+    let (program_id, data) = transaction_context.get_return_data();
+
+    InvokeContextSnapshot {
+        accounts,
+        return_data: (*program_id, data.to_vec()),
+    }
+}
+
+/// Reset the accounts and return data of the current invoke context to the snapshot, which has
+/// to be taken from a transaction context with the same accounts.
+pub fn restore(snapshot: &InvokeContextSnapshot) -> Result<(), InstructionError> {
+    let transaction_context = &mut get_invoke_context().transaction_context;
+    if snapshot.accounts.len() != transaction_context.get_number_of_accounts() as usize {
+        return Err(InstructionError::NotEnoughAccountKeys);
+    }
+    for (index, account) in snapshot.accounts.iter().enumerate() {
+        *transaction_context
+            .get_account_at_index(index as u16)?
+            .try_borrow_mut()
+            .map_err(|_| InstructionError::AccountBorrowFailed)? = account.clone();
+    }
+    let (program_id, data) = &snapshot.return_data;
+    transaction_context.set_return_data(*program_id, data.clone())
+}
+
+/// Changes of an account since a snapshot, each field holds the value in the snapshot and the
+/// current value, `None` when it did not change.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AccountDiff {
+    pub pubkey: Pubkey,
+    pub owner: Option<(Pubkey, Pubkey)>,
+    pub lamports: Option<(u64, u64)>,
+    pub data_len: Option<(usize, usize)>,
+    pub data: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+fn changed<T: PartialEq>(before: T, after: T) -> Option<(T, T)> {
+    (before != after).then_some((before, after))
+}
+
+/// Compare the accounts of the current invoke context with the snapshot, only the accounts
+/// which changed are listed, in the order of the transaction context.
+pub fn diff_accounts(snapshot: &InvokeContextSnapshot) -> Vec<AccountDiff> {
+    let transaction_context = &get_invoke_context().transaction_context;
+    snapshot
+        .accounts
+        .iter()
+        .enumerate()
+        .filter_map(|(index, before)| {
+            let index = index as IndexOfAccount;
+            let pubkey = *transaction_context
+                .get_key_of_account_at_index(index)
+                .ok()?;
+            let after = transaction_context
+                .get_account_at_index(index)
+                .ok()?
+                .borrow();
+            let diff = AccountDiff {
+                pubkey,
+                owner: changed(*before.owner(), *after.owner()),
+                lamports: changed(before.lamports(), after.lamports()),
+                data_len: changed(before.data().len(), after.data().len()),
+                data: changed(before.data(), after.data())
+                    .map(|(before, after)| (before.to_vec(), after.to_vec())),
+            };
+            (diff.owner.is_some() || diff.lamports.is_some() || diff.data.is_some()).then_some(diff)
+        })
+        .collect()
+}
+
+/// Describe each field in which the account differs from the expected one.
+pub(crate) fn account_mismatches(
+    actual: &AccountSharedData,
+    expected: &AccountSharedData,
+) -> Vec<String> {
+    let mut mismatches = Vec::new();
+    if actual.owner() != expected.owner() {
+        mismatches.push(format!("owner: {} != {}", actual.owner(), expected.owner()));
+    }
+    if actual.lamports() != expected.lamports() {
+        mismatches.push(format!(
+            "lamports: {} != {}",
+            actual.lamports(),
+            expected.lamports()
+        ));
+    }
+    if actual.data().len() != expected.data().len() {
+        mismatches.push(format!(
+            "data_len: {} != {}",
+            actual.data().len(),
+            expected.data().len()
+        ));
+    }
+    if let Some(offset) = actual
+        .data()
+        .iter()
+        .zip(expected.data())
+        .position(|(actual, expected)| actual != expected)
+    {
+        mismatches.push(format!(
+            "data: first difference at byte {offset}, {:#04x} != {:#04x}",
+            actual.data()[offset],
+            expected.data()[offset]
+        ));
+    }
+    #[allow(deprecated)]
+    if actual.executable() != expected.executable() {
+        mismatches.push(format!(
+            "executable: {} != {}",
+            actual.executable(),
+            expected.executable()
+        ));
+    }
+    if actual.rent_epoch() != expected.rent_epoch() {
+        mismatches.push(format!(
+            "rent_epoch: {} != {}",
+            actual.rent_epoch(),
+            expected.rent_epoch()
+        ));
+    }
+    mismatches
+}
+
+pub(crate) fn panic_on_account_mismatch(
+    pubkey: &Pubkey,
+    actual: &AccountSharedData,
+    expected: &AccountSharedData,
+) {
+    let mismatches = account_mismatches(actual, expected);
+    if !mismatches.is_empty() {
+        panic!(
+            "account {pubkey} differs from the expected one (actual != expected):\n  {}",
+            mismatches.join("\n  ")
+        );
+    }
+}
+
+/// Assert that the account in the transaction context of the current invoke context equals the
+/// expected one, the panic message lists the fields which differ.
+pub fn assert_account_eq(pubkey: &Pubkey, expected: &AccountSharedData) {
+    let account = get_account(pubkey)
+        .unwrap_or_else(|| panic!("account {pubkey} is not in the transaction context"));
+    panic_on_account_mismatch(pubkey, &account, expected);
+}
+
+/// Current state of an account of the transaction context, `None` if it is not one of its
+/// accounts. Changes made through account infos are seen once copied back, i.e. after a CPI or
+/// the end of the instruction.
+pub fn get_account(pubkey: &Pubkey) -> Option<AccountSharedData> {
+    let transaction_context = &get_invoke_context().transaction_context;
+    let index_in_transaction = transaction_context.find_index_of_account(pubkey)?;
+    let account = transaction_context
+        .get_account_at_index(index_in_transaction)
+        .ok()?;
+    let account = account.borrow().clone();
+    Some(account)
+}
+
+/// Push an instruction of the given program onto the instruction stack of the current invoke
+/// context, as the runtime does before invoking a program. The program and the accounts have
+/// to be accounts of the transaction context, the accounts keep the privileges of their metas.
+pub fn push_instruction_context(
+    program_id: &Pubkey,
+    accounts: &[AccountMeta],
+    instruction_data: &[u8],
+) -> Result<(), ProgramError> {
+    let invoke_context = get_invoke_context();
+    let transaction_context = &invoke_context.transaction_context;
+    let caller_instruction_context = transaction_context.get_current_instruction_context().ok();
+
+    let program_index = transaction_context
+        .find_index_of_account(program_id)
+        .ok_or(to_program_error(InstructionError::MissingAccount))?;
+
+    let mut instruction_accounts: Vec<InstructionAccount> = Vec::with_capacity(accounts.len());
+    for (index_in_instruction, account_meta) in accounts.iter().enumerate() {
+        let index_in_transaction = transaction_context
+            .find_index_of_account(&account_meta.pubkey)
+            .ok_or(to_program_error(InstructionError::MissingAccount))?;
+        let index_in_caller = match caller_instruction_context {
+            Some(instruction_context) => instruction_context
+                .find_index_of_instruction_account(transaction_context, &account_meta.pubkey)
+                .ok_or(to_program_error(InstructionError::MissingAccount))?,
+            None => index_in_transaction,
+        };
+        let index_in_callee = instruction_accounts
+            .iter()
+            .position(|instruction_account| {
+                instruction_account.index_in_transaction == index_in_transaction
+            })
+            .unwrap_or(index_in_instruction) as IndexOfAccount;
+        instruction_accounts.push(InstructionAccount {
+            index_in_transaction,
+            index_in_caller,
+            index_in_callee,
+            is_signer: account_meta.is_signer,
+            is_writable: account_meta.is_writable,
+        });
+    }
+
+    invoke_context
+        .transaction_context
+        .get_next_instruction_context()
+        .map_err(to_program_error)?
+        .configure(&[program_index], &instruction_accounts, instruction_data);
+    invoke_context
+        .transaction_context
+        .push()
+        .map_err(to_program_error)
+}
+
+/// Pop the instruction pushed last by `push_instruction_context`.
+pub fn pop_instruction_context() -> Result<(), ProgramError> {
+    get_invoke_context()
+        .transaction_context
+        .pop()
+        .map_err(to_program_error)
+}
+
+/// Process the instruction as a CPI of the current instruction, dispatched to the program
+/// accounts at the given transaction indices instead of the one `prepare_instruction` resolves
+/// the program id to, e.g. to test loader edge cases. The accounts are resolved and checked as
+/// for a CPI without signers, the program id has to be an account of the current instruction.
+/// Unlike `sol_invoke_signed` no account infos are involved, the callee works on the accounts of
+/// the transaction context.
+pub fn process_instruction_with_program_indices(
+    instruction: &Instruction,
+    program_indices: &[IndexOfAccount],
+) -> Result<(), ProgramError> {
+    let invoke_context = get_invoke_context();
+    let stable_instruction = StableInstruction {
+        accounts: instruction.accounts.clone().into(),
+        data: Vec::new().into(),
+        program_id: instruction.program_id,
+    };
+    let (instruction_accounts, _) = invoke_context
+        .prepare_instruction(&stable_instruction, &[])
+        .map_err(to_program_error)?;
+
+    let mut compute_units_consumed = 0;
+    invoke_context
+        .process_instruction(
+            &instruction.data,
+            &instruction_accounts,
+            program_indices,
+            &mut compute_units_consumed,
+            &mut ExecuteTimings::default(),
+        )
+        .map_err(to_program_error)
+}
@@ -1,9 +1,54 @@
+// The stubs transmute the types of the program into their own, so they may only be built against
+// the one solana_program version the program uses. The stubs of Solana 2 are in the
+// trident-syscall-stubs-v2 crate, this crate has no `v2` feature.
+#[cfg(not(feature = "v1"))]
+compile_error!("the `v1` feature has to be enabled, the v2 stubs are in trident-syscall-stubs-v2");
+
 pub mod syscall_stubs;
 pub use syscall_stubs::*;
 
 pub mod invoke_context;
 pub use invoke_context::*;
 
+pub mod processor;
+pub use processor::*;
+
+pub mod sysvar_cache;
+pub use sysvar_cache::*;
+
+pub mod fault_injection;
+pub use fault_injection::*;
+
+pub mod recorder;
+pub use recorder::*;
+
+pub mod config;
+pub use config::*;
+
+pub mod metrics;
+pub use metrics::*;
+
+pub mod coverage;
+pub use coverage::*;
+
+pub mod overrides;
+pub use overrides::*;
+
+pub mod account_infos;
+pub use account_infos::*;
+
+pub mod transaction_context;
+pub use transaction_context::*;
+
+pub mod log_framing;
+pub use log_framing::*;
+
+pub mod log_buffer;
+pub use log_buffer::*;
+
+mod memory;
+pub(crate) use memory::*;
+
 // Trait to convert between types that are not directly compatible
 pub trait TridentTryFrom<T>: Sized {
     /// The type returned in the event of a conversion error.
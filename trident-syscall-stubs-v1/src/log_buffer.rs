@@ -0,0 +1,34 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use solana_log_collector::LogCollector;
+
+thread_local! {
+    static LOG_BUFFER: RefCell<VecDeque<String>> = const { RefCell::new(VecDeque::new()) };
+}
+
+/// Messages of `sol_log` and `sol_log_data` retained on this thread while the logs are captured
+/// into the ring buffer through `StubConfig::log_ring_buffer`, oldest first.
+pub fn buffered_logs() -> Vec<String> {
+    LOG_BUFFER.with(|buffer| buffer.borrow().iter().cloned().collect())
+}
+
+pub fn clear_buffered_logs() {
+    LOG_BUFFER.with(|buffer| buffer.borrow_mut().clear());
+}
+
+/// The messages are formatted by a log collector of their own, so that the retained lines match
+/// those of the runtime, and the oldest lines are evicted once the capacity is exceeded.
+pub(crate) fn buffer_log(capacity: usize, log: impl FnOnce(&Option<Rc<RefCell<LogCollector>>>)) {
+    let log_collector = LogCollector::new_ref_with_limit(None);
+    log(&Some(log_collector.clone()));
+    let messages = std::mem::take(&mut log_collector.borrow_mut().messages);
+    LOG_BUFFER.with(|buffer| {
+        let buffer = &mut *buffer.borrow_mut();
+        buffer.extend(messages);
+        while buffer.len() > capacity {
+            buffer.pop_front();
+        }
+    });
+}
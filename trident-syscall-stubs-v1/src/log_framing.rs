@@ -0,0 +1,94 @@
+use std::str::FromStr;
+
+use solana_sdk::pubkey::Pubkey;
+
+/// How an invocation ended according to its closing log line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InvokeOutcome {
+    /// `Program <address> success`
+    Success,
+    /// `Program <address> failed: <error>`, with the error as logged.
+    Failure(String),
+}
+
+/// Invocation of a program as framed in the logs by its `invoke` line and its `success` or
+/// `failed` line, together with the messages it logged and the programs it invoked.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvokeFrame {
+    pub program_id: Pubkey,
+    /// Stack height of the `invoke [<depth>]` line, 1 for a top level instruction.
+    pub stack_height: usize,
+    /// None if the logs end before the invocation is closed, e.g. when they were truncated.
+    pub outcome: Option<InvokeOutcome>,
+    /// Messages logged between the invoke and the closing line, without those of the inner
+    /// invocations.
+    pub logs: Vec<String>,
+    pub inner_invocations: Vec<InvokeFrame>,
+}
+
+/// Nest the captured logs, e.g. `ProcessedInstruction::logs`, into the invocations framed by the
+/// `stable_log` lines, so that harnesses can assert the framing without matching strings.
+/// Messages outside of any invocation, e.g. `Log truncated`, are dropped. The program messages
+/// and the framing are only logged to the same collector without a custom log collector and
+/// without `StubConfig::log_ring_buffer`.
+pub fn parse_invoke_framing(logs: &[String]) -> Vec<InvokeFrame> {
+    let mut frames = Vec::new();
+    let mut open_frames: Vec<InvokeFrame> = Vec::new();
+    for log in logs {
+        if let Some((program_id, stack_height)) = parse_invoke_line(log) {
+            open_frames.push(InvokeFrame {
+                program_id,
+                stack_height,
+                outcome: None,
+                logs: Vec::new(),
+                inner_invocations: Vec::new(),
+            });
+            continue;
+        }
+        let Some(frame) = open_frames.last_mut() else {
+            continue;
+        };
+        match parse_closing_line(log, &frame.program_id) {
+            Some(outcome) => {
+                let mut frame = open_frames.pop().expect("frame is open");
+                frame.outcome = Some(outcome);
+                close_frame(frame, &mut open_frames, &mut frames);
+            }
+            None => frame.logs.push(log.clone()),
+        }
+    }
+    while let Some(frame) = open_frames.pop() {
+        close_frame(frame, &mut open_frames, &mut frames);
+    }
+    frames
+}
+
+fn close_frame(frame: InvokeFrame, open_frames: &mut [InvokeFrame], frames: &mut Vec<InvokeFrame>) {
+    match open_frames.last_mut() {
+        Some(caller) => caller.inner_invocations.push(frame),
+        None => frames.push(frame),
+    }
+}
+
+fn parse_invoke_line(log: &str) -> Option<(Pubkey, usize)> {
+    let (program_id, rest) = log.strip_prefix("Program ")?.split_once(' ')?;
+    let stack_height = rest.strip_prefix("invoke [")?.strip_suffix(']')?;
+    Some((
+        Pubkey::from_str(program_id).ok()?,
+        stack_height.parse().ok()?,
+    ))
+}
+
+/// Only the lines of the innermost open invocation close it, the closing lines of other programs
+/// are messages like any other.
+fn parse_closing_line(log: &str, program_id: &Pubkey) -> Option<InvokeOutcome> {
+    let (key, rest) = log.strip_prefix("Program ")?.split_once(' ')?;
+    if key != program_id.to_string() {
+        return None;
+    }
+    if rest == "success" {
+        return Some(InvokeOutcome::Success);
+    }
+    rest.strip_prefix("failed: ")
+        .map(|error| InvokeOutcome::Failure(error.to_string()))
+}
@@ -0,0 +1,66 @@
+use std::cell::RefCell;
+use std::ops::Range;
+
+use solana_program::account_info::AccountInfo;
+use solana_program::entrypoint::MAX_PERMITTED_DATA_INCREASE;
+
+use crate::strict_memory_enabled;
+
+thread_local! {
+    static ACCOUNT_DATA_REGIONS: RefCell<Vec<Range<usize>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Keeps the account data regions of the input of a running program registered for the checks
+/// of `StubConfig::strict_memory`, the programs of nested CPIs add theirs on top.
+pub(crate) struct AccountDataRegionsGuard {
+    previous_len: usize,
+}
+
+impl AccountDataRegionsGuard {
+    pub(crate) fn new(account_infos: &[AccountInfo]) -> Self {
+        ACCOUNT_DATA_REGIONS.with(|regions| {
+            let regions = &mut *regions.borrow_mut();
+            let previous_len = regions.len();
+            if strict_memory_enabled() {
+                // The data may grow in place up to MAX_PERMITTED_DATA_INCREASE through realloc
+                regions.extend(account_infos.iter().map(|account_info| {
+                    let data = account_info.data.borrow();
+                    let start = data.as_ptr() as usize;
+                    start..start + data.len() + MAX_PERMITTED_DATA_INCREASE
+                }));
+            }
+            Self { previous_len }
+        })
+    }
+}
+
+impl Drop for AccountDataRegionsGuard {
+    fn drop(&mut self) {
+        ACCOUNT_DATA_REGIONS.with(|regions| regions.borrow_mut().truncate(self.previous_len));
+    }
+}
+
+/// With `StubConfig::strict_memory`, panic as the program would be aborted by an access
+/// violation if the range starts in the data of an account but ends beyond the space it may
+/// grow into. Other memory, e.g. the stack or the heap, is not known to the stubs and is not
+/// checked.
+pub(crate) fn check_memory_range(syscall: &str, addr: *const u8, len: usize) {
+    if !strict_memory_enabled() {
+        return;
+    }
+    let start = addr as usize;
+    let end = start.saturating_add(len);
+    ACCOUNT_DATA_REGIONS.with(|regions| {
+        if let Some(region) = regions
+            .borrow()
+            .iter()
+            .find(|region| region.contains(&start) && end > region.end)
+        {
+            panic!(
+                "Access violation in {syscall}: {len} bytes at {start:#x} exceed the account data \
+                 at {:#x}..{:#x}",
+                region.start, region.end
+            );
+        }
+    });
+}
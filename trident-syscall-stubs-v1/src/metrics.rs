@@ -0,0 +1,69 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+
+use solana_sdk::pubkey::Pubkey;
+
+use crate::cpi_compute_metrics_enabled;
+use crate::syscall_metrics_enabled;
+
+thread_local! {
+    static SYSCALL_COUNTS: RefCell<HashMap<&'static str, u64>> = RefCell::new(HashMap::new());
+    static CPI_COMPUTE_UNITS: RefCell<BTreeMap<usize, u64>> = const { RefCell::new(BTreeMap::new()) };
+    static CPI_COUNTS: RefCell<HashMap<Pubkey, u64>> = RefCell::new(HashMap::new());
+}
+
+/// Number of calls of each syscall (e.g. "sol_invoke_signed") made on this thread while the
+/// metrics are enabled through `StubConfig::syscall_metrics`. The counters are per thread like
+/// the rest of the stub state, the calls made on other threads, e.g. by other tests, are neither
+/// counted nor seen here, a campaign fuzzing on several threads has to merge their counts.
+pub fn syscall_counts() -> HashMap<&'static str, u64> {
+    SYSCALL_COUNTS.with(|counts| counts.borrow().clone())
+}
+
+pub fn reset_syscall_counts() {
+    SYSCALL_COUNTS.with(|counts| counts.borrow_mut().clear());
+}
+
+pub(crate) fn count_syscall(syscall: &'static str) {
+    if syscall_metrics_enabled() {
+        SYSCALL_COUNTS.with(|counts| *counts.borrow_mut().entry(syscall).or_default() += 1);
+    }
+}
+
+/// Number of CPIs made on this thread to each program id while the metrics are enabled through
+/// `StubConfig::syscall_metrics`, including those failing before the callee is run.
+pub fn cpi_counts() -> HashMap<Pubkey, u64> {
+    CPI_COUNTS.with(|counts| counts.borrow().clone())
+}
+
+pub fn reset_cpi_counts() {
+    CPI_COUNTS.with(|counts| counts.borrow_mut().clear());
+}
+
+pub(crate) fn count_cpi(program_id: &Pubkey) {
+    if syscall_metrics_enabled() {
+        CPI_COUNTS.with(|counts| *counts.borrow_mut().entry(*program_id).or_default() += 1);
+    }
+}
+
+/// Compute units consumed by the CPIs made on this thread by their stack height, 2 for a CPI of
+/// the top level instruction, while the metrics are enabled through
+/// `StubConfig::cpi_compute_metrics`. The units of a CPI include those of the CPIs it made.
+pub fn cpi_compute_units() -> BTreeMap<usize, u64> {
+    CPI_COMPUTE_UNITS.with(|units| units.borrow().clone())
+}
+
+pub fn reset_cpi_compute_units() {
+    CPI_COMPUTE_UNITS.with(|units| units.borrow_mut().clear());
+}
+
+pub(crate) fn add_cpi_compute_units(stack_height: usize, compute_units: u64) {
+    if cpi_compute_metrics_enabled() {
+        CPI_COMPUTE_UNITS.with(|units| {
+            let units = &mut *units.borrow_mut();
+            let total = units.entry(stack_height).or_default();
+            *total = total.saturating_add(compute_units);
+        });
+    }
+}
@@ -0,0 +1,55 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use solana_sdk::pubkey::Pubkey;
+
+/// Custom implementation of a syscall, preferred by the stubs over their own one. Calls of an
+/// overridden syscall are still counted and recorded.
+#[allow(clippy::type_complexity)]
+pub enum SyscallOverride {
+    Log(Box<dyn Fn(&str)>),
+    LogData(Box<dyn Fn(&[&[u8]])>),
+    RemainingComputeUnits(Box<dyn Fn() -> u64>),
+    GetReturnData(Box<dyn Fn() -> Option<(Pubkey, Vec<u8>)>>),
+    SetReturnData(Box<dyn Fn(&[u8])>),
+    GetStackHeight(Box<dyn Fn() -> u64>),
+}
+
+impl SyscallOverride {
+    /// Name of the overridden syscall, e.g. "sol_get_return_data".
+    pub fn syscall(&self) -> &'static str {
+        match self {
+            Self::Log(_) => "sol_log",
+            Self::LogData(_) => "sol_log_data",
+            Self::RemainingComputeUnits(_) => "sol_remaining_compute_units",
+            Self::GetReturnData(_) => "sol_get_return_data",
+            Self::SetReturnData(_) => "sol_set_return_data",
+            Self::GetStackHeight(_) => "sol_get_stack_height",
+        }
+    }
+}
+
+thread_local! {
+    static SYSCALL_OVERRIDES: RefCell<HashMap<&'static str, Rc<SyscallOverride>>> =
+        RefCell::new(HashMap::new());
+}
+
+/// Override a syscall for the programs run on this thread, replacing a previous override of
+/// the same syscall.
+pub fn set_syscall_override(syscall_override: SyscallOverride) {
+    SYSCALL_OVERRIDES.with(|overrides| {
+        overrides
+            .borrow_mut()
+            .insert(syscall_override.syscall(), Rc::new(syscall_override));
+    });
+}
+
+pub fn clear_syscall_overrides() {
+    SYSCALL_OVERRIDES.with(|overrides| overrides.borrow_mut().clear());
+}
+
+/// Cloned out of the registry, so that the override may call the stubs or register overrides.
+pub(crate) fn get_syscall_override(syscall: &'static str) -> Option<Rc<SyscallOverride>> {
+    SYSCALL_OVERRIDES.with(|overrides| overrides.borrow().get(syscall).cloned())
+}
@@ -0,0 +1,563 @@
+#![allow(clippy::not_unsafe_ptr_arg_deref)]
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::mem::size_of;
+
+use solana_sdk::account::AccountSharedData;
+use solana_sdk::account::ReadableAccount;
+use solana_sdk::bpf_loader;
+use solana_sdk::bpf_loader_deprecated;
+use solana_sdk::bpf_loader_upgradeable;
+use solana_sdk::feature_set::FeatureSet;
+use solana_sdk::hash::Hash;
+use solana_sdk::instruction::AccountMeta;
+use solana_sdk::instruction::InstructionError;
+use solana_sdk::native_loader;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::system_program;
+use solana_sdk::transaction_context::IndexOfAccount;
+use solana_sdk::transaction_context::InstructionAccount;
+use solana_sdk::transaction_context::InstructionContext;
+use solana_sdk::transaction_context::TransactionAccount;
+use solana_sdk::transaction_context::TransactionContext;
+
+use solana_program::entrypoint::deserialize;
+use solana_program::entrypoint::ProcessInstruction;
+use solana_program::entrypoint::BPF_ALIGN_OF_U128;
+use solana_program::entrypoint::MAX_PERMITTED_DATA_INCREASE;
+use solana_program::entrypoint::NON_DUP_MARKER;
+use solana_program::program::MAX_RETURN_DATA;
+use solana_program::program_error::ProgramError;
+
+use solana_log_collector::LogCollector;
+use solana_program_runtime::declare_process_instruction;
+use solana_program_runtime::invoke_context::BuiltinFunctionWithContext;
+use solana_program_runtime::invoke_context::EnvironmentConfig;
+use solana_program_runtime::invoke_context::InvokeContext;
+use solana_program_runtime::loaded_programs::ProgramCacheEntry;
+use solana_program_runtime::loaded_programs::ProgramCacheForTxBatch;
+use solana_program_runtime::sysvar_cache::SysvarCache;
+use solana_system_program::system_processor;
+use solana_timings::ExecuteTimings;
+
+use std::sync::Arc;
+
+use crate::check_accounts_data_growth;
+use crate::configured_compute_budget;
+use crate::panic_on_account_mismatch;
+use crate::set_stubs_v1;
+use crate::set_thread_context;
+use crate::to_program_error;
+use crate::AccountDataRegionsGuard;
+use crate::TransactionContextBuilder;
+use crate::TridentTryFrom;
+
+/// Compute units consumed by every invocation of a program registered through the processor.
+pub const PROGRAM_ENTRYPOINT_COMPUTE_UNITS: u64 = 1;
+
+/// Loaders whose programs are dispatched to the entrypoints registered through the processor.
+pub const PROGRAM_LOADERS: [Pubkey; 3] = [
+    bpf_loader::id(),
+    bpf_loader_deprecated::id(),
+    bpf_loader_upgradeable::id(),
+];
+
+/// Builtin programs available to every processed instruction, including its CPIs.
+pub const BUILTIN_PROGRAMS: [(Pubkey, BuiltinFunctionWithContext); 1] =
+    [(system_program::id(), system_processor::Entrypoint::vm)];
+
+thread_local! {
+    static PROGRAM_ENTRYPOINTS: RefCell<HashMap<Pubkey, ProcessInstruction>> =
+        RefCell::new(HashMap::new());
+    static SEEDED_RETURN_DATA: RefCell<Option<(Pubkey, Vec<u8>)>> = const { RefCell::new(None) };
+    /// Failure of a syscall which cannot return it to the program, see `fail_program`.
+    static SYSCALL_FAILURE: RefCell<Option<ProgramError>> = const { RefCell::new(None) };
+    static REGISTERED_BUILTINS: RefCell<Vec<(Pubkey, BuiltinFunctionWithContext)>> =
+        const { RefCell::new(Vec::new()) };
+}
+
+/// Register a builtin program for the instructions processed on this thread, next to the
+/// `BUILTIN_PROGRAMS`, e.g. a test-only native program declared with
+/// `declare_process_instruction!` to be called through CPI without deploying an SBF program.
+/// The builtin function is `Entrypoint::vm` of the declared entrypoint, which has to declare
+/// non-zero default compute units as builtins have to consume some. Registering the same program
+/// id again replaces the builtin.
+pub fn add_builtin_program(program_id: &Pubkey, builtin_function: BuiltinFunctionWithContext) {
+    REGISTERED_BUILTINS.with(|builtins| {
+        let builtins = &mut *builtins.borrow_mut();
+        builtins.retain(|(builtin_id, _)| builtin_id != program_id);
+        builtins.push((*program_id, builtin_function));
+    });
+}
+
+pub fn clear_builtin_programs() {
+    REGISTERED_BUILTINS.with(|builtins| builtins.borrow_mut().clear());
+}
+
+/// The `BUILTIN_PROGRAMS` followed by the builtins registered through `add_builtin_program`.
+fn builtin_programs() -> Vec<(Pubkey, BuiltinFunctionWithContext)> {
+    let mut builtin_programs = BUILTIN_PROGRAMS.to_vec();
+    REGISTERED_BUILTINS.with(|builtins| builtin_programs.extend(builtins.borrow().iter().copied()));
+    builtin_programs
+}
+
+/// Register the native entrypoint of a program, programs owned by the BPF loader are dispatched
+/// to the entrypoint registered for their program id.
+pub fn set_program_entrypoint(program_id: &Pubkey, entrypoint: ProcessInstruction) {
+    PROGRAM_ENTRYPOINTS.with(|entrypoints| {
+        entrypoints.borrow_mut().insert(*program_id, entrypoint);
+    });
+}
+
+/// Seed the return data seen by the program of the next instruction processed on this thread at
+/// its start, as if it was left by a previous instruction. Fails with `InvalidArgument` for data
+/// longer than `MAX_RETURN_DATA`.
+pub fn set_initial_return_data(program_id: &Pubkey, data: &[u8]) -> Result<(), InstructionError> {
+    if data.len() > MAX_RETURN_DATA {
+        return Err(InstructionError::InvalidArgument);
+    }
+    SEEDED_RETURN_DATA.with(|seeded| seeded.replace(Some((*program_id, data.to_vec()))));
+    Ok(())
+}
+
+/// Fail the program currently running with the given error once its entrypoint returns, for a
+/// syscall which aborts the program on chain but has no result to return the error through.
+pub(crate) fn fail_program(err: ProgramError) {
+    SYSCALL_FAILURE.with(|failure| failure.replace(Some(err)));
+}
+
+fn get_program_entrypoint(program_id: &Pubkey) -> Option<ProcessInstruction> {
+    PROGRAM_ENTRYPOINTS.with(|entrypoints| entrypoints.borrow().get(program_id).copied())
+}
+
+declare_process_instruction!(
+    TridentEntrypoint,
+    PROGRAM_ENTRYPOINT_COMPUTE_UNITS,
+    |invoke_context| {
+        // The runtime clears the return data before every program, the seeded one replaces it
+        // for the top level instruction only
+        if invoke_context.get_stack_height() == 1 {
+            if let Some((program_id, data)) = SEEDED_RETURN_DATA.with(|seeded| seeded.take()) {
+                invoke_context
+                    .transaction_context
+                    .set_return_data(program_id, data)?;
+            }
+        }
+        let transaction_context = &invoke_context.transaction_context;
+        let instruction_context = transaction_context.get_current_instruction_context()?;
+        let program_id = *instruction_context.get_last_program_key(transaction_context)?;
+        let entrypoint =
+            get_program_entrypoint(&program_id).ok_or(InstructionError::UnsupportedProgramId)?;
+
+        // The entrypoint receives the input in the same layout as an on-chain program, so that
+        // AccountInfo::realloc and the CPI copy-back work on the serialized account data
+        let mut input = serialize_parameters(transaction_context, instruction_context)?;
+        let (program_id, account_infos, instruction_data) =
+            unsafe { deserialize(input.as_mut_ptr() as *mut u8) };
+
+        let account_data_regions_guard = AccountDataRegionsGuard::new(&account_infos);
+        let result = entrypoint(program_id, &account_infos, instruction_data);
+        // The program is aborted by the failed syscall on chain, what it returned afterwards does
+        // not matter
+        let result = match SYSCALL_FAILURE.with(|failure| failure.take()) {
+            Some(err) => Err(err),
+            None => result,
+        };
+        result.map_err(|err| {
+            InstructionError::try_from_custom(err).unwrap_or_else(|err| panic!("{}", err))
+        })?;
+        drop(account_data_regions_guard);
+
+        // Re-fetch the instruction context, the CPIs of the program pushed to the trace
+        let transaction_context = &invoke_context.transaction_context;
+        let instruction_context = transaction_context.get_current_instruction_context()?;
+
+        for index_in_instruction in 0..instruction_context.get_number_of_instruction_accounts() {
+            if instruction_context
+                .is_instruction_account_duplicate(index_in_instruction)?
+                .is_some()
+            {
+                continue;
+            }
+            // Read-only accounts are compared like the others, a change fails the instruction with
+            // ReadonlyLamportChange, ReadonlyDataModified or ModifiedProgramId as in the runtime
+            let mut borrowed_account = instruction_context
+                .try_borrow_instruction_account(transaction_context, index_in_instruction)?;
+            let account_info = &account_infos[index_in_instruction as usize];
+            if borrowed_account.get_lamports() != account_info.lamports() {
+                borrowed_account.set_lamports(account_info.lamports())?;
+            }
+            let account_info_data = account_info.data.borrow();
+            // As the deserialization of the runtime, unchanged data passes even if the program
+            // could not have changed it, while an illegal change fails the instruction. Unchanged
+            // data is not written, so that the caller of a CPI sees that the buffer is unchanged
+            if borrowed_account.get_data() != *account_info_data {
+                borrowed_account.can_data_be_resized(account_info_data.len())?;
+                check_accounts_data_growth(
+                    transaction_context,
+                    borrowed_account.get_data().len(),
+                    account_info_data.len(),
+                )?;
+                borrowed_account.can_data_be_changed()?;
+                borrowed_account.set_data_from_slice(&account_info_data)?;
+            }
+            if borrowed_account.get_owner().as_ref() != account_info.owner.as_ref() {
+                borrowed_account.set_owner(account_info.owner.as_ref())?;
+            }
+        }
+
+        Ok(())
+    }
+);
+
+/// Serialize the current instruction in the aligned input layout of the BPF loader, the buffer
+/// is backed by u64 words so that the deserialized references are properly aligned.
+fn serialize_parameters(
+    transaction_context: &TransactionContext,
+    instruction_context: &InstructionContext,
+) -> Result<Vec<u64>, InstructionError> {
+    let mut input: Vec<u8> = Vec::new();
+
+    let number_of_instruction_accounts = instruction_context.get_number_of_instruction_accounts();
+    input.extend_from_slice(&(number_of_instruction_accounts as u64).to_le_bytes());
+
+    for index_in_instruction in 0..number_of_instruction_accounts {
+        if let Some(index_in_callee) =
+            instruction_context.is_instruction_account_duplicate(index_in_instruction)?
+        {
+            input.push(index_in_callee as u8);
+            input.extend_from_slice(&[0u8; 7]);
+            continue;
+        }
+        let borrowed_account = instruction_context
+            .try_borrow_instruction_account(transaction_context, index_in_instruction)?;
+        input.push(NON_DUP_MARKER);
+        input.push(borrowed_account.is_signer() as u8);
+        input.push(borrowed_account.is_writable() as u8);
+        #[allow(deprecated)]
+        input.push(borrowed_account.is_executable() as u8);
+        input.extend_from_slice(&[0u8; 4]);
+        input.extend_from_slice(borrowed_account.get_key().as_ref());
+        input.extend_from_slice(borrowed_account.get_owner().as_ref());
+        input.extend_from_slice(&borrowed_account.get_lamports().to_le_bytes());
+        input.extend_from_slice(&(borrowed_account.get_data().len() as u64).to_le_bytes());
+        input.extend_from_slice(borrowed_account.get_data());
+        input.resize(input.len() + MAX_PERMITTED_DATA_INCREASE, 0);
+        input.resize(input.len().next_multiple_of(BPF_ALIGN_OF_U128), 0);
+        input.extend_from_slice(&borrowed_account.get_rent_epoch().to_le_bytes());
+    }
+
+    let instruction_data = instruction_context.get_instruction_data();
+    input.extend_from_slice(&(instruction_data.len() as u64).to_le_bytes());
+    input.extend_from_slice(instruction_data);
+    input.extend_from_slice(
+        instruction_context
+            .get_last_program_key(transaction_context)?
+            .as_ref(),
+    );
+
+    let mut words = vec![0u64; input.len().div_ceil(size_of::<u64>())];
+    for (word, bytes) in words.iter_mut().zip(input.chunks(size_of::<u64>())) {
+        let mut word_bytes = [0u8; size_of::<u64>()];
+        word_bytes[..bytes.len()].copy_from_slice(bytes);
+        *word = u64::from_ne_bytes(word_bytes);
+    }
+    Ok(words)
+}
+
+/// Builtins are executed by the program cache entry of their own program id, which the runtime
+/// looks up for program accounts owned by the native loader.
+fn builtin_program_account() -> AccountSharedData {
+    let mut program_account = AccountSharedData::new(1, 0, &native_loader::id());
+    #[allow(deprecated)]
+    solana_sdk::account::WritableAccount::set_executable(&mut program_account, true);
+    program_account
+}
+
+/// Instruction recorded in the instruction trace of the transaction context, together with the
+/// CPIs it made.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TracedInstruction {
+    pub program_id: Pubkey,
+    pub account_keys: Vec<Pubkey>,
+    pub data: Vec<u8>,
+    pub inner_instructions: Vec<TracedInstruction>,
+}
+
+/// Walk the instruction trace of the transaction context and nest every instruction under the
+/// instruction that invoked it, by their stack heights.
+pub fn instruction_trace(
+    transaction_context: &TransactionContext,
+) -> Result<Vec<TracedInstruction>, InstructionError> {
+    let mut trace: Vec<TracedInstruction> = Vec::new();
+    for index_in_trace in 0..transaction_context.get_instruction_trace_length() {
+        let instruction_context =
+            transaction_context.get_instruction_context_at_index_in_trace(index_in_trace)?;
+        let account_keys = (0..instruction_context.get_number_of_instruction_accounts())
+            .map(|index_in_instruction| {
+                let index_in_transaction = instruction_context
+                    .get_index_of_instruction_account_in_transaction(index_in_instruction)?;
+                transaction_context
+                    .get_key_of_account_at_index(index_in_transaction)
+                    .copied()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let traced_instruction = TracedInstruction {
+            program_id: *instruction_context.get_last_program_key(transaction_context)?,
+            account_keys,
+            data: instruction_context.get_instruction_data().to_vec(),
+            inner_instructions: Vec::new(),
+        };
+
+        let mut level = &mut trace;
+        for _ in 1..instruction_context.get_stack_height() {
+            let Some(index_of_caller) = level.len().checked_sub(1) else {
+                break;
+            };
+            level = &mut level[index_of_caller].inner_instructions;
+        }
+        level.push(traced_instruction);
+    }
+    Ok(trace)
+}
+
+/// State of a single processed instruction.
+pub struct ProcessedInstruction {
+    /// Result of the instruction as reported by the runtime.
+    pub result: Result<(), InstructionError>,
+    /// Accounts of the instruction after processing, without the program account.
+    pub accounts: Vec<TransactionAccount>,
+    /// Messages recorded by the log collector.
+    pub logs: Vec<String>,
+    /// The instruction and the CPIs it made, see `instruction_trace`.
+    pub trace: Vec<TracedInstruction>,
+}
+
+impl ProcessedInstruction {
+    /// Assert that the account after processing equals the expected one, the panic message
+    /// lists the fields which differ.
+    pub fn assert_account_eq(&self, pubkey: &Pubkey, expected: &AccountSharedData) {
+        let (_, account) = self
+            .accounts
+            .iter()
+            .find(|(key, _)| key == pubkey)
+            .unwrap_or_else(|| panic!("account {pubkey} is not an account of the instruction"));
+        panic_on_account_mismatch(pubkey, account, expected);
+    }
+}
+
+/// Process a single instruction of the given program against the given accounts. A minimal
+/// transaction context is built around the instruction, the stubs are installed and the
+/// program is executed through its native entrypoint. Accounts of the `BUILTIN_PROGRAMS` and of
+/// the builtins registered through `add_builtin_program` are replaced by their native loader
+/// program accounts.
+pub fn process_one_instruction(
+    program_id: &Pubkey,
+    entrypoint: ProcessInstruction,
+    accounts: &[(AccountMeta, AccountSharedData)],
+    instruction_data: &[u8],
+) -> ProcessedInstruction {
+    set_program_entrypoint(program_id, entrypoint);
+
+    let builtin_programs = builtin_programs();
+
+    // Duplicate accounts share the transaction account and its merged privileges
+    let mut transaction_accounts: Vec<TransactionAccount> = Vec::with_capacity(accounts.len());
+    let mut account_metas: Vec<AccountMeta> = Vec::with_capacity(accounts.len());
+    for (account_meta, account) in accounts {
+        match account_metas
+            .iter_mut()
+            .find(|meta| meta.pubkey == account_meta.pubkey)
+        {
+            Some(meta) => {
+                meta.is_signer |= account_meta.is_signer;
+                meta.is_writable |= account_meta.is_writable;
+            }
+            None => {
+                let account = if builtin_programs
+                    .iter()
+                    .any(|(builtin_id, _)| *builtin_id == account_meta.pubkey)
+                {
+                    builtin_program_account()
+                } else {
+                    account.clone()
+                };
+                account_metas.push(account_meta.clone());
+                transaction_accounts.push((account_meta.pubkey, account));
+            }
+        }
+    }
+
+    let mut instruction_accounts: Vec<InstructionAccount> = Vec::with_capacity(accounts.len());
+    for (index_in_instruction, (account_meta, _)) in accounts.iter().enumerate() {
+        let index_in_transaction = account_metas
+            .iter()
+            .position(|meta| meta.pubkey == account_meta.pubkey)
+            .unwrap() as IndexOfAccount;
+        let index_in_callee = instruction_accounts
+            .iter()
+            .position(|instruction_account| {
+                instruction_account.index_in_transaction == index_in_transaction
+            })
+            .unwrap_or(index_in_instruction) as IndexOfAccount;
+        let merged_meta = &account_metas[index_in_transaction as usize];
+        instruction_accounts.push(InstructionAccount {
+            index_in_transaction,
+            index_in_caller: index_in_transaction,
+            index_in_callee,
+            is_signer: merged_meta.is_signer,
+            is_writable: merged_meta.is_writable,
+        });
+    }
+
+    let program_index = transaction_accounts.len() as IndexOfAccount;
+    let mut program_account = AccountSharedData::new(0, 0, &bpf_loader::id());
+    #[allow(deprecated)]
+    solana_sdk::account::WritableAccount::set_executable(&mut program_account, true);
+    transaction_accounts.push((*program_id, program_account));
+
+    let compute_budget = configured_compute_budget();
+    let mut transaction_context = TransactionContextBuilder::new()
+        .accounts(&transaction_accounts)
+        .build();
+
+    let mut sysvar_cache = SysvarCache::default();
+    sysvar_cache.fill_missing_entries(|pubkey, callback| {
+        if let Some(index) = transaction_context.find_index_of_account(pubkey) {
+            callback(
+                transaction_context
+                    .get_account_at_index(index)
+                    .unwrap()
+                    .borrow()
+                    .data(),
+            );
+        }
+    });
+
+    let mut program_cache_for_tx_batch = ProgramCacheForTxBatch::default();
+    for loader_id in PROGRAM_LOADERS {
+        program_cache_for_tx_batch.replenish(
+            loader_id,
+            Arc::new(ProgramCacheEntry::new_builtin(0, 0, TridentEntrypoint::vm)),
+        );
+    }
+    for (builtin_id, builtin_function) in builtin_programs {
+        program_cache_for_tx_batch.replenish(
+            builtin_id,
+            Arc::new(ProgramCacheEntry::new_builtin(0, 0, builtin_function)),
+        );
+    }
+
+    let environment_config = EnvironmentConfig::new(
+        Hash::default(),
+        0,
+        0,
+        &|_| 0,
+        Arc::new(FeatureSet::all_enabled()),
+        &sysvar_cache,
+    );
+    let log_collector = LogCollector::new_ref();
+
+    let mut invoke_context = InvokeContext::new(
+        &mut transaction_context,
+        &mut program_cache_for_tx_batch,
+        environment_config,
+        Some(log_collector.clone()),
+        compute_budget,
+    );
+
+    let invoke_context_guard = set_thread_context(&mut invoke_context);
+    set_stubs_v1();
+
+    let result = invoke_context.process_instruction(
+        instruction_data,
+        &instruction_accounts,
+        &[program_index],
+        &mut 0,
+        &mut ExecuteTimings::default(),
+    );
+
+    drop(invoke_context_guard);
+    drop(invoke_context);
+
+    let trace = instruction_trace(&transaction_context).unwrap();
+
+    let keys = (0..program_index)
+        .map(|index| {
+            *transaction_context
+                .get_key_of_account_at_index(index)
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+    let accounts = keys
+        .into_iter()
+        .zip(transaction_context.deconstruct_without_keys().unwrap())
+        .collect();
+    let logs = std::mem::take(&mut log_collector.borrow_mut().messages);
+
+    ProcessedInstruction {
+        result,
+        accounts,
+        logs,
+        trace,
+    }
+}
+
+/// Instruction of a sequence processed by `process_instructions_collecting_errors`.
+pub struct InstructionInput {
+    pub program_id: Pubkey,
+    pub entrypoint: ProcessInstruction,
+    pub accounts: Vec<(AccountMeta, AccountSharedData)>,
+    pub data: Vec<u8>,
+}
+
+/// Instructions processed by `process_instructions_collecting_errors`.
+pub struct ProcessedInstructions {
+    /// Each of the instructions, in order.
+    pub instructions: Vec<ProcessedInstruction>,
+    /// Index and error of each failed instruction.
+    pub errors: Vec<(usize, ProgramError)>,
+}
+
+/// Process the instructions one after another, each seeing the accounts left by the previous
+/// successful ones, and keep going after a failure to collect the errors of all of them. This
+/// is meant for diagnostics, unlike a transaction the sequence is not atomic: the changes of the
+/// instructions that succeeded are kept, only those of the failed instructions are dropped.
+pub fn process_instructions_collecting_errors(
+    instructions: &[InstructionInput],
+) -> ProcessedInstructions {
+    let mut latest_accounts: HashMap<Pubkey, AccountSharedData> = HashMap::new();
+    let mut processed_instructions = Vec::with_capacity(instructions.len());
+    let mut errors = Vec::new();
+
+    for (index, instruction) in instructions.iter().enumerate() {
+        let accounts = instruction
+            .accounts
+            .iter()
+            .map(|(account_meta, account)| {
+                let account = latest_accounts
+                    .get(&account_meta.pubkey)
+                    .unwrap_or(account)
+                    .clone();
+                (account_meta.clone(), account)
+            })
+            .collect::<Vec<_>>();
+
+        let processed_instruction = process_one_instruction(
+            &instruction.program_id,
+            instruction.entrypoint,
+            &accounts,
+            &instruction.data,
+        );
+        match &processed_instruction.result {
+            Ok(()) => latest_accounts.extend(processed_instruction.accounts.iter().cloned()),
+            Err(err) => errors.push((index, to_program_error(err.clone()))),
+        }
+        processed_instructions.push(processed_instruction);
+    }
+
+    ProcessedInstructions {
+        instructions: processed_instructions,
+        errors,
+    }
+}
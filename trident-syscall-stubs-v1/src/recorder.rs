@@ -0,0 +1,96 @@
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::fs::File;
+use std::io;
+use std::io::BufReader;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::Path;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use solana_sdk::pubkey::Pubkey;
+
+/// Version of the format written by `dump_trace`, bumped on every change of `SyscallEvent` that
+/// older traces cannot be read with.
+pub const TRACE_FORMAT_VERSION: u32 = 1;
+
+/// Syscall made by a program, recorded while the recorder is running.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "syscall")]
+pub enum SyscallEvent {
+    Log { message: String },
+    LogData { fields: Vec<Vec<u8>> },
+    RemainingComputeUnits,
+    GetSysvar { sysvar_id: Pubkey },
+    InvokeSigned { program_id: Pubkey },
+    GetReturnData,
+    SetReturnData { len: usize },
+    GetStackHeight,
+}
+
+thread_local! {
+    static RECORDING: Cell<bool> = const { Cell::new(false) };
+    static SYSCALL_EVENTS: RefCell<Vec<SyscallEvent>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Start recording the syscalls made on this thread, previously recorded events are kept.
+pub fn start_recording() {
+    RECORDING.with(|recording| recording.set(true));
+}
+
+pub fn stop_recording() {
+    RECORDING.with(|recording| recording.set(false));
+}
+
+/// Drain the recorded syscalls in the order they were made.
+pub fn take_syscall_events() -> Vec<SyscallEvent> {
+    SYSCALL_EVENTS.with(|events| std::mem::take(&mut *events.borrow_mut()))
+}
+
+/// The event is only constructed while recording, so a disabled recorder costs a single check.
+pub(crate) fn record_syscall(event: impl FnOnce() -> SyscallEvent) {
+    if RECORDING.with(|recording| recording.get()) {
+        SYSCALL_EVENTS.with(|events| events.borrow_mut().push(event()));
+    }
+}
+
+/// On disk format of a trace: a JSON object with the format version and the events, each an
+/// object with the name of its variant in `syscall` and its fields, e.g.
+/// `{"syscall":"SetReturnData","len":4}`.
+#[derive(Serialize, Deserialize)]
+struct Trace {
+    version: u32,
+    events: Vec<SyscallEvent>,
+}
+
+/// Write the events, e.g. those taken after a crashing input, to the file at the given path, so
+/// that the syscall sequence can be replayed on another machine with `load_trace`.
+pub fn dump_trace(path: impl AsRef<Path>, events: &[SyscallEvent]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer(
+        &mut writer,
+        &Trace {
+            version: TRACE_FORMAT_VERSION,
+            events: events.to_vec(),
+        },
+    )?;
+    writer.flush()
+}
+
+/// Read the events written by `dump_trace`, a trace of another format version fails with
+/// `io::ErrorKind::InvalidData`.
+pub fn load_trace(path: impl AsRef<Path>) -> io::Result<Vec<SyscallEvent>> {
+    let trace: Trace = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+    if trace.version != TRACE_FORMAT_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "trace format version {} is not {TRACE_FORMAT_VERSION}",
+                trace.version
+            ),
+        ));
+    }
+    Ok(trace.events)
+}
@@ -1,91 +1,411 @@
 #![allow(clippy::not_unsafe_ptr_arg_deref)]
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::hash::Hasher;
+use std::mem::align_of;
+use std::mem::offset_of;
+use std::mem::size_of;
 use std::mem::transmute;
+use std::rc::Rc;
 use std::sync::Arc;
 use std::sync::Once;
 
 use solana_sdk::account_info::AccountInfo;
 use solana_sdk::entrypoint::SUCCESS;
+use solana_sdk::epoch_rewards::EpochRewards;
+use solana_sdk::instruction::AccountMeta;
 use solana_sdk::instruction::Instruction;
 use solana_sdk::instruction::InstructionError;
+use solana_sdk::program::MAX_RETURN_DATA;
+use solana_sdk::program_error::INVALID_ARGUMENT;
 use solana_sdk::program_error::UNSUPPORTED_SYSVAR;
 use solana_sdk::pubkey::Pubkey;
+use solana_sdk::pubkey::PubkeyError;
 use solana_sdk::stable_layout::stable_instruction::StableInstruction;
 use solana_sdk::sysvar::Sysvar;
+use solana_sdk::transaction::TransactionError;
 
 use solana_program::program_error::ProgramError;
 use solana_program::program_stubs;
 use solana_program::program_stubs::set_syscall_stubs;
 
+use solana_compute_budget::compute_budget::ComputeBudget;
+
+use solana_log_collector::ic_logger_msg;
+use solana_log_collector::LogCollector;
+use solana_program_runtime::solana_sbpf::vm::ContextObject;
 use solana_program_runtime::stable_log;
 use solana_timings::ExecuteTimings;
 
+use crate::add_cpi_compute_units;
+use crate::buffer_log;
+use crate::capture_logs_enabled;
+use crate::charge_compute_units_enabled;
+use crate::check_accounts_data_growth;
+use crate::check_memory_range;
+use crate::configured_cpi_enter_hook;
+use crate::configured_log_ring_buffer;
+use crate::configured_max_cpi_depth;
+use crate::configured_prepare_instruction_hook;
+use crate::configured_return_data_hook;
+use crate::count_cpi;
+use crate::count_syscall;
+use crate::current_program_id;
+use crate::fail_program;
+use crate::feed_log_coverage;
 use crate::get_invoke_context;
+use crate::get_log_collector;
+use crate::get_return_data;
+use crate::get_syscall_override;
+use crate::is_invoke_context_set;
+use crate::no_panic_enabled;
+use crate::processed_sibling_instructions;
+use crate::read_sysvar;
+use crate::record_syscall;
+use crate::set_stub_config;
+use crate::take_fault;
+use crate::StubConfig;
+use crate::SyscallEvent;
+use crate::SyscallOverride;
+use crate::SysvarCache;
 use crate::TridentTryFrom;
 
 static ONCE: Once = Once::new();
 
+/// The stubs reinterpret the types of solana_program 1.18 as those of solana_sdk, which is only
+/// sound while both have the same layout. A dependency tree where they differ fails to build
+/// here instead of corrupting memory at run time.
+macro_rules! assert_same_layout {
+    ($program_type:ty, $sdk_type:ty $(, $field:ident)*) => {
+        const _: () = {
+            assert!(size_of::<$program_type>() == size_of::<$sdk_type>());
+            assert!(align_of::<$program_type>() == align_of::<$sdk_type>());
+            $(assert!(offset_of!($program_type, $field) == offset_of!($sdk_type, $field));)*
+        };
+    };
+}
+
+assert_same_layout!(solana_program::pubkey::Pubkey, Pubkey);
+assert_same_layout!(
+    solana_program::instruction::AccountMeta,
+    AccountMeta,
+    pubkey,
+    is_signer,
+    is_writable
+);
+assert_same_layout!(
+    solana_program::instruction::Instruction,
+    Instruction,
+    program_id,
+    accounts,
+    data
+);
+assert_same_layout!(
+    solana_program::account_info::AccountInfo<'static>,
+    AccountInfo<'static>,
+    key,
+    lamports,
+    data,
+    owner,
+    rent_epoch,
+    is_signer,
+    is_writable,
+    executable
+);
+
 pub fn set_stubs_v1() {
     ONCE.call_once(|| {
         set_syscall_stubs(Box::new(TridentSyscallStubs {}));
     });
 }
 
+/// Install the stubs with the given configuration for the calling thread.
+pub fn set_stubs_v1_with_config(config: StubConfig) {
+    set_stub_config(config);
+    set_stubs_v1();
+}
+
+/// Make a CPI without signers from the harness, as the program on top of the instruction stack
+/// would through `invoke`, e.g. with the account infos of an `AccountInfoStorage` after
+/// `push_instruction_context`. The changes made by the callee are copied back into the account
+/// infos as for a program, and account infos which are already borrowed are rejected as by
+/// `solana_program::program::invoke`, which this forwards to.
+pub fn invoke(
+    instruction: &solana_program::instruction::Instruction,
+    account_infos: &[solana_program::account_info::AccountInfo<'_>],
+) -> Result<(), ProgramError> {
+    set_stubs_v1();
+    solana_program::program::invoke(instruction, account_infos)
+}
+
+/// Write the sysvar to `var_addr`, which has to point to a buffer large enough to hold `T` and
+/// aligned for `T`. The size of the buffer cannot be checked here, but null and misaligned
+/// pointers are rejected with `INVALID_ARGUMENT` instead of being written through. A sysvar
+/// missing from the cache is reported as `UNSUPPORTED_SYSVAR`.
 fn get_sysvar<T: Default + Sysvar + Sized + serde::de::DeserializeOwned + Clone>(
+    syscall: &'static str,
     sysvar: Result<Arc<T>, InstructionError>,
     var_addr: *mut u8,
 ) -> u64 {
+    write_sysvar(
+        syscall,
+        T::id(),
+        sysvar.map(|sysvar_data| T::clone(&sysvar_data)),
+        var_addr,
+    )
+}
+
+/// Same as `get_sysvar`, for sysvars whose layout differs between the cache and the program.
+fn write_sysvar<T>(
+    syscall: &'static str,
+    sysvar_id: Pubkey,
+    sysvar: Result<T, InstructionError>,
+    var_addr: *mut u8,
+) -> u64 {
+    count_syscall(syscall);
+    record_syscall(|| SyscallEvent::GetSysvar { sysvar_id });
+    // The cost of every sysvar syscall in the runtime, the base cost and one unit per byte of the
+    // sysvar, charged before the sysvar is looked up as there
+    consume_compute_units(|compute_budget| {
+        compute_budget
+            .sysvar_base_cost
+            .saturating_add(size_of::<T>() as u64)
+    });
+    if let Some(fault) = take_fault(syscall) {
+        return fault.into_return_code();
+    }
+    let var_addr = var_addr as *mut T;
+    if var_addr.is_null() || !var_addr.is_aligned() {
+        return INVALID_ARGUMENT;
+    }
     match sysvar {
         Ok(sysvar_data) => unsafe {
-            *(var_addr as *mut _ as *mut T) = T::clone(&sysvar_data);
+            var_addr.write(sysvar_data);
             SUCCESS
         },
-        Err(_) => UNSUPPORTED_SYSVAR,
+        Err(InstructionError::UnsupportedSysvar) => UNSUPPORTED_SYSVAR,
+        // Any other failure, e.g. of a malformed sysvar account, is reported as the corresponding
+        // return code, so it is not mistaken for a missing sysvar
+        Err(err) => to_program_error(err).into(),
     }
 }
 
+/// The programs see the EpochRewards of solana 1.17, which has no active flag and ends the
+/// distribution at a block height instead. The distribution ends once every partition has been
+/// distributed, so an active distribution always ends after its starting block. Outside of a
+/// distribution the sysvar does not exist there, an inactive one is reported as missing.
+fn to_program_epoch_rewards(
+    epoch_rewards: &EpochRewards,
+) -> Result<solana_program::epoch_rewards::EpochRewards, InstructionError> {
+    if !epoch_rewards.active {
+        return Err(InstructionError::UnsupportedSysvar);
+    }
+    Ok(solana_program::epoch_rewards::EpochRewards {
+        total_rewards: epoch_rewards.total_rewards,
+        distributed_rewards: epoch_rewards.distributed_rewards,
+        distribution_complete_block_height: epoch_rewards
+            .distribution_starting_block_height
+            .saturating_add(epoch_rewards.num_partitions),
+    })
+}
+
+/// Charge the compute units the runtime charges for a syscall, with
+/// `StubConfig::charge_compute_units` set. Unlike the runtime, the stubs cannot abort the program
+/// once its budget is exhausted, the meter stays at zero and the next CPI fails with
+/// `ComputationalBudgetExceeded` instead.
+fn consume_compute_units(compute_units: impl FnOnce(&ComputeBudget) -> u64) {
+    if !charge_compute_units_enabled() || !is_invoke_context_set() {
+        return;
+    }
+    let invoke_context = get_invoke_context();
+    let compute_units = compute_units(invoke_context.get_compute_budget());
+    invoke_context.consume(compute_units);
+}
+
+/// Cost of a memory syscall operating on `n` bytes.
+fn mem_op_compute_units(compute_budget: &ComputeBudget, n: usize) -> u64 {
+    compute_budget
+        .mem_op_base_cost
+        .max((n as u64) / compute_budget.cpi_bytes_per_unit)
+}
+
+/// Failure of the stubs themselves rather than of the program, it panics unless
+/// `StubConfig::no_panic` is set, in which case the syscall fails with `program_error`.
+/// A borrow of an account info held by the caller of a CPI is an error of the program, not one
+/// of these: the CPI fails with `AccountBorrowFailed` whichever way it was made.
+fn internal_error<E: std::fmt::Debug>(err: E, program_error: ProgramError) -> ProgramError {
+    if !no_panic_enabled() {
+        panic!("{err:?}");
+    }
+    program_error
+}
+
+/// The data of distinct accounts never overlaps on chain, account infos aliasing each other's
+/// data would be silently corrupted by the copy back after the CPI. Duplicates of an account
+/// share the same data and are allowed.
+fn check_account_data_aliasing(
+    account_infos: &[AccountInfo],
+    log_collector: &Option<Rc<RefCell<LogCollector>>>,
+) -> Result<(), ProgramError> {
+    // Read through the pointer of the RefCell, the caller may hold borrows of the data
+    let mut ranges = account_infos
+        .iter()
+        .filter_map(|account_info| {
+            let data = unsafe { &*account_info.data.as_ptr() };
+            let start = data.as_ptr() as usize;
+            (!data.is_empty()).then_some((start, start + data.len(), account_info.key))
+        })
+        .collect::<Vec<_>>();
+    ranges.sort_unstable();
+    ranges.dedup();
+
+    for pair in ranges.windows(2) {
+        let (_, end, key) = pair[0];
+        let (start, _, other_key) = pair[1];
+        if start < end {
+            ic_logger_msg!(
+                log_collector,
+                "Account data of {} overlaps with account data of {}",
+                key,
+                other_key
+            );
+            return Err(ProgramError::InvalidArgument);
+        }
+    }
+    Ok(())
+}
+
 pub struct TridentSyscallStubs;
 
 impl program_stubs::SyscallStubs for TridentSyscallStubs {
+    // Off chain solana_program has no sol_log_pubkey and sol_log_64 stubs, Pubkey::log and
+    // sol_log_64 format their arguments and log them here. The formatting is the same as the
+    // runtime's (base58 and comma separated hex), so the messages match the on chain ones.
     fn sol_log(&self, message: &str) {
-        let invoke_context = get_invoke_context();
-        let log_collector = invoke_context.get_log_collector();
+        count_syscall("sol_log");
+        record_syscall(|| SyscallEvent::Log {
+            message: message.to_string(),
+        });
+        if let Some(SyscallOverride::Log(log)) = get_syscall_override("sol_log").as_deref() {
+            return log(message);
+        }
+        consume_compute_units(|compute_budget| {
+            compute_budget.syscall_base_cost.max(message.len() as u64)
+        });
+        feed_log_coverage(|hasher| hasher.write(message.as_bytes()));
+        if !capture_logs_enabled() {
+            return;
+        }
+        if let Some(capacity) = configured_log_ring_buffer() {
+            return buffer_log(capacity, |log_collector| {
+                stable_log::program_log(log_collector, message)
+            });
+        }
+        let log_collector = get_log_collector();
 
         stable_log::program_log(&log_collector, message);
     }
 
+    // Both log syscalls go through the log collector of the invoke context, which drops messages
+    // over its byte limit and records "Log truncated" in the same way as the runtime does.
+    // The runtime has no limit of its own on the fields of sol_log_data, oversized data is only
+    // capped by the byte limit and paid for in compute units.
+    fn sol_log_data(&self, fields: &[&[u8]]) {
+        count_syscall("sol_log_data");
+        record_syscall(|| SyscallEvent::LogData {
+            fields: fields.iter().map(|field| field.to_vec()).collect(),
+        });
+        if let Some(SyscallOverride::LogData(log_data)) =
+            get_syscall_override("sol_log_data").as_deref()
+        {
+            return log_data(fields);
+        }
+        consume_compute_units(|compute_budget| {
+            let field_cost = compute_budget
+                .syscall_base_cost
+                .saturating_mul(fields.len() as u64);
+            let data_cost = fields.iter().map(|field| field.len() as u64).sum::<u64>();
+            compute_budget
+                .syscall_base_cost
+                .saturating_add(field_cost)
+                .saturating_add(data_cost)
+        });
+        feed_log_coverage(|hasher| {
+            for field in fields {
+                hasher.write(field);
+            }
+        });
+        if !capture_logs_enabled() {
+            return;
+        }
+        if let Some(capacity) = configured_log_ring_buffer() {
+            return buffer_log(capacity, |log_collector| {
+                stable_log::program_data(log_collector, fields)
+            });
+        }
+        let log_collector = get_log_collector();
+
+        stable_log::program_data(&log_collector, fields);
+    }
+
+    fn sol_remaining_compute_units(&self) -> u64 {
+        count_syscall("sol_remaining_compute_units");
+        record_syscall(|| SyscallEvent::RemainingComputeUnits);
+        if let Some(SyscallOverride::RemainingComputeUnits(remaining_compute_units)) =
+            get_syscall_override("sol_remaining_compute_units").as_deref()
+        {
+            return remaining_compute_units();
+        }
+        consume_compute_units(|compute_budget| compute_budget.syscall_base_cost);
+        get_invoke_context().get_remaining()
+    }
+
     fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
-        get_sysvar(get_invoke_context().get_sysvar_cache().get_rent(), var_addr)
+        get_sysvar(
+            "sol_get_rent_sysvar",
+            read_sysvar(SysvarCache::get_rent),
+            var_addr,
+        )
     }
     fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
         get_sysvar(
-            get_invoke_context().get_sysvar_cache().get_clock(),
+            "sol_get_clock_sysvar",
+            read_sysvar(SysvarCache::get_clock),
             var_addr,
         )
     }
 
     fn sol_get_epoch_schedule_sysvar(&self, var_addr: *mut u8) -> u64 {
         get_sysvar(
-            get_invoke_context().get_sysvar_cache().get_epoch_schedule(),
+            "sol_get_epoch_schedule_sysvar",
+            read_sysvar(SysvarCache::get_epoch_schedule),
             var_addr,
         )
     }
 
     fn sol_get_epoch_rewards_sysvar(&self, var_addr: *mut u8) -> u64 {
-        get_sysvar(
-            get_invoke_context().get_sysvar_cache().get_epoch_rewards(),
+        write_sysvar(
+            "sol_get_epoch_rewards_sysvar",
+            solana_sdk::sysvar::epoch_rewards::id(),
+            read_sysvar(SysvarCache::get_epoch_rewards)
+                .and_then(|epoch_rewards| to_program_epoch_rewards(&epoch_rewards)),
             var_addr,
         )
     }
     #[allow(deprecated)]
     fn sol_get_fees_sysvar(&self, var_addr: *mut u8) -> u64 {
-        get_sysvar(get_invoke_context().get_sysvar_cache().get_fees(), var_addr)
+        get_sysvar(
+            "sol_get_fees_sysvar",
+            read_sysvar(SysvarCache::get_fees),
+            var_addr,
+        )
     }
 
     fn sol_get_last_restart_slot(&self, var_addr: *mut u8) -> u64 {
         get_sysvar(
-            get_invoke_context()
-                .get_sysvar_cache()
-                .get_last_restart_slot(),
+            "sol_get_last_restart_slot",
+            read_sysvar(SysvarCache::get_last_restart_slot),
             var_addr,
         )
     }
@@ -95,9 +415,42 @@ impl program_stubs::SyscallStubs for TridentSyscallStubs {
         account_infos: &[solana_program::account_info::AccountInfo<'_>],
         signers_seeds: &[&[&[u8]]],
     ) -> std::result::Result<(), solana_program::program_error::ProgramError> {
+        count_syscall("sol_invoke_signed");
+        count_cpi(&Pubkey::new_from_array(instruction.program_id.to_bytes()));
+        record_syscall(|| SyscallEvent::InvokeSigned {
+            program_id: Pubkey::new_from_array(instruction.program_id.to_bytes()),
+        });
+        if let Some(fault) = take_fault("sol_invoke_signed") {
+            return Err(fault.into_program_error());
+        }
         let instruction = unsafe {
             transmute::<&solana_program::instruction::Instruction, &Instruction>(instruction)
         };
+        if let Some(hook) = configured_cpi_enter_hook() {
+            hook(
+                &instruction.program_id,
+                get_invoke_context().get_stack_height() + 1,
+                instruction,
+            )?;
+        }
+        // The stack height of the caller is the number of CPIs the callee would be nested in
+        if configured_max_cpi_depth()
+            .is_some_and(|max_cpi_depth| get_invoke_context().get_stack_height() > max_cpi_depth)
+        {
+            return Err(to_program_error(InstructionError::CallDepth));
+        }
+        // Charged up front as by the runtime, a CPI the budget cannot pay for is not made
+        let invoke_context = get_invoke_context();
+        let compute_budget = invoke_context.get_compute_budget();
+        let compute_units = compute_budget
+            .invoke_units
+            .saturating_add((instruction.data.len() as u64) / compute_budget.cpi_bytes_per_unit);
+        if charge_compute_units_enabled() && invoke_context.consume_checked(compute_units).is_err()
+        {
+            return Err(to_program_error(
+                InstructionError::ComputationalBudgetExceeded,
+            ));
+        }
 
         let account_infos = unsafe {
             transmute::<&[solana_program::account_info::AccountInfo<'_>], &[AccountInfo<'_>]>(
@@ -105,197 +458,473 @@ impl program_stubs::SyscallStubs for TridentSyscallStubs {
             )
         };
 
-        let instruction = StableInstruction::from(instruction.clone());
+        // prepare_instruction only reads the program id and the accounts, the instruction data is
+        // passed to the callee by reference so it is not cloned
+        let stable_instruction = StableInstruction {
+            accounts: instruction.accounts.clone().into(),
+            data: Vec::new().into(),
+            program_id: instruction.program_id,
+        };
         let invoke_context = get_invoke_context();
         let log_collector = invoke_context.get_log_collector();
+        check_account_data_aliasing(account_infos, &log_collector)?;
         let transaction_context = &invoke_context.transaction_context;
 
         let instruction_context = transaction_context
             .get_current_instruction_context()
-            .map_err(|err| {
-                ProgramError::try_from_custom(err).unwrap_or_else(|err| panic!("{}", err))
-            })?;
+            .map_err(to_program_error)?;
 
         let caller = instruction_context
             .get_last_program_key(transaction_context)
-            .map_err(|err| {
-                ProgramError::try_from_custom(err).unwrap_or_else(|err| panic!("{}", err))
-            })?;
+            .map_err(to_program_error)?;
 
+        // create_program_address checks the seeds against MAX_SEEDS and MAX_SEED_LEN before
+        // hashing them, oversized seeds fail with MaxSeedLengthExceeded as in the runtime
+        // A plain invoke passes no seeds and so signs for no PDA, an account of the callee is then
+        // a signer only if it is one in the caller
         let signers = signers_seeds
             .iter()
-            .map(|seeds| Pubkey::create_program_address(seeds, caller).unwrap())
-            .collect::<Vec<_>>();
+            .map(|seeds| {
+                Pubkey::create_program_address(seeds, caller).map_err(|err| match err {
+                    PubkeyError::MaxSeedLengthExceeded => ProgramError::MaxSeedLengthExceeded,
+                    PubkeyError::InvalidSeeds => ProgramError::InvalidSeeds,
+                    PubkeyError::IllegalOwner => ProgramError::IllegalOwner,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
 
+        // Rejects accounts that are writable or signer in the callee without being so in the
+        // caller (or signed by `signers`) with `InstructionError::PrivilegeEscalation`, and a
+        // callee which is not an account of the caller with `InstructionError::MissingAccount`
+        // like the runtime does. A callee without a loaded program fails in process_instruction
+        // with `InstructionError::UnsupportedProgramId`.
         let (instruction_accounts, program_indices) = invoke_context
-            .prepare_instruction(&instruction, &signers)
-            .map_err(|err| {
-                ProgramError::try_from_custom(err).unwrap_or_else(|err| panic!("{}", err))
-            })?;
+            .prepare_instruction(&stable_instruction, &signers)
+            .map_err(to_program_error)?;
+
+        if let Some(hook) = configured_prepare_instruction_hook() {
+            hook(instruction, &instruction_accounts, &program_indices);
+        }
 
         // Copy caller's account_info modifications into invoke_context accounts
         let transaction_context = &invoke_context.transaction_context;
 
         let instruction_context = transaction_context
             .get_current_instruction_context()
-            .map_err(|err| {
-                ProgramError::try_from_custom(err).unwrap_or_else(|err| panic!("{}", err))
-            })?;
+            .map_err(to_program_error)?;
 
         let mut account_indices = Vec::with_capacity(instruction_accounts.len());
 
-        for instruction_account in instruction_accounts.iter() {
+        // Duplicate account infos resolve to the first one, as the linear search did before. The
+        // map is only used for lookups, the accounts are copied in and back in the order of the
+        // instruction accounts, so the result does not depend on the order of the account infos
+        let mut account_info_indices = HashMap::with_capacity(account_infos.len());
+        for (account_info_index, account_info) in account_infos.iter().enumerate() {
+            account_info_indices
+                .entry(account_info.unsigned_key())
+                .or_insert(account_info_index);
+        }
+
+        for (index_in_instruction, instruction_account) in instruction_accounts.iter().enumerate() {
             let account_key = transaction_context
                 .get_key_of_account_at_index(instruction_account.index_in_transaction)
-                .map_err(|err| {
-                    ProgramError::try_from_custom(err).unwrap_or_else(|err| panic!("{}", err))
-                })?;
-            let account_info_index = account_infos
-                .iter()
-                .position(|account_info| account_info.unsigned_key() == account_key)
-                .ok_or(InstructionError::MissingAccount)
-                .map_err(|err| {
-                    ProgramError::try_from_custom(err).unwrap_or_else(|err| panic!("{}", err))
-                })?;
+                .map_err(to_program_error)?;
+            // An account of the instruction without an account info fails with MissingAccount as
+            // in the runtime. A callee given fewer accounts than it needs fails on its own, e.g.
+            // with NotEnoughAccountKeys from next_account_info.
+            let Some(&account_info_index) = account_info_indices.get(account_key) else {
+                ic_logger_msg!(
+                    log_collector,
+                    "Instruction references an unknown account {}",
+                    account_key
+                );
+                return Err(to_program_error(InstructionError::MissingAccount));
+            };
             let account_info = &account_infos[account_info_index];
+            // The runtime charges the data of each account passed to the callee once, the data of
+            // executable accounts is not copied and so not charged
+            if charge_compute_units_enabled()
+                && instruction_account.index_in_callee as usize == index_in_instruction
+                && !account_info.executable
+            {
+                // The caller holding a borrow of the data fails the CPI as on chain, e.g. through
+                // invoke_signed_unchecked which does not check the borrows
+                let data_len = account_info
+                    .try_borrow_data()
+                    .map_err(|_| ProgramError::AccountBorrowFailed)?
+                    .len();
+                let compute_units =
+                    (data_len as u64) / invoke_context.get_compute_budget().cpi_bytes_per_unit;
+                if invoke_context.consume_checked(compute_units).is_err() {
+                    return Err(to_program_error(
+                        InstructionError::ComputationalBudgetExceeded,
+                    ));
+                }
+            }
             let mut borrowed_account = instruction_context
                 .try_borrow_instruction_account(
                     transaction_context,
                     instruction_account.index_in_caller,
                 )
-                .map_err(|err| {
-                    ProgramError::try_from_custom(err).unwrap_or_else(|err| panic!("{}", err))
-                })?;
+                .map_err(to_program_error)?;
             if borrowed_account.get_lamports() != account_info.lamports() {
                 borrowed_account
                     .set_lamports(account_info.lamports())
-                    .map_err(|err| {
-                        ProgramError::try_from_custom(err).unwrap_or_else(|err| panic!("{}", err))
-                    })?;
+                    .map_err(to_program_error)?;
             }
             // Beware the program it retursn if from different crate version
-            let account_info_data = account_info.try_borrow_data().unwrap();
-            // The redundant check helps to avoid the expensive data comparison if we can
+            let account_info_data = account_info
+                .try_borrow_data()
+                .map_err(|_| ProgramError::AccountBorrowFailed)?;
+            // The same validation as in the runtime: a resize always changes the data, so an
+            // illegal resize fails with the mapped error like any other illegal change, while
+            // unchanged data passes even if the caller could not have changed it. Checking the
+            // permissions first avoids the expensive data comparison when a change is allowed.
             match borrowed_account
                 .can_data_be_resized(account_info_data.len())
+                .and_then(|_| {
+                    check_accounts_data_growth(
+                        transaction_context,
+                        borrowed_account.get_data().len(),
+                        account_info_data.len(),
+                    )
+                })
                 .and_then(|_| borrowed_account.can_data_be_changed())
             {
+                // A write rejected for a reason the checks above do not cover is returned to the
+                // caller the same way
                 Ok(()) => borrowed_account
                     .set_data_from_slice(&account_info_data)
-                    .map_err(|err| {
-                        ProgramError::try_from_custom(err).unwrap_or_else(|err| panic!("{}", err))
-                    })?,
+                    .map_err(to_program_error)?,
+                // The caller changed data it may not change, e.g. of a read-only account
+                // (ReadonlyDataModified), of an account it does not own
+                // (ExternalAccountDataModified) or its size (AccountDataSizeChanged). Ownership
+                // is always enforced, as on chain
                 Err(err) if borrowed_account.get_data() != *account_info_data => {
-                    panic!("{err:?}");
+                    return Err(to_program_error(err));
                 }
                 _ => {}
             }
             // Change the owner at the end so that we are allowed to change the lamports and data before
+            // An owner the caller may not assign, e.g. of an account it does not own or of an
+            // executable account, fails with ModifiedProgramId as in the runtime. IllegalOwner is
+            // only returned on chain for program addresses derived with an illegal owner.
             if borrowed_account.get_owner() != account_info.owner {
                 borrowed_account
                     .set_owner(account_info.owner.as_ref())
-                    .map_err(|err| {
-                        ProgramError::try_from_custom(err).unwrap_or_else(|err| panic!("{}", err))
-                    })?;
+                    .map_err(to_program_error)?;
             }
+            drop(borrowed_account);
             if instruction_account.is_writable {
-                account_indices.push((instruction_account.index_in_caller, account_info_index));
+                // Holding the data buffer makes any write of the callee replace it, a buffer still
+                // shared after the CPI is unchanged
+                let data_before_cpi = transaction_context
+                    .get_account_at_index(instruction_account.index_in_transaction)
+                    .map_err(to_program_error)?
+                    .borrow()
+                    .data_clone();
+                account_indices.push((
+                    instruction_account.index_in_caller,
+                    instruction_account.index_in_transaction,
+                    account_info_index,
+                    data_before_cpi,
+                ));
             }
         }
 
         let mut compute_units_consumed = 0;
 
-        stable_log::program_invoke(
-            &log_collector,
-            &instruction.program_id,
-            invoke_context.get_stack_height(),
-        );
+        // The invoke, success and failure lines of the callee are logged by process_instruction,
+        // for builtins as for programs run through the entrypoint, as in the validator
 
-        invoke_context
-            .process_instruction(
-                &instruction.data,
-                &instruction_accounts,
-                &program_indices,
-                &mut compute_units_consumed,
-                &mut ExecuteTimings::default(),
-            )
-            .map_err(|err| {
-                ProgramError::try_from_custom(err).unwrap_or_else(|err| panic!("{}", err))
-            })?;
+        // The transaction context compares the lamport sums of the instruction accounts when the
+        // callee is pushed and popped, an unbalanced callee fails with UnbalancedInstruction
+        let result = invoke_context.process_instruction(
+            &instruction.data,
+            &instruction_accounts,
+            &program_indices,
+            &mut compute_units_consumed,
+            &mut ExecuteTimings::default(),
+        );
+        add_cpi_compute_units(
+            invoke_context.get_stack_height() + 1,
+            compute_units_consumed,
+        );
+        // The failure line of the callee has been logged already, returning early leaves the
+        // failure line of the caller to its own process_instruction
+        result.map_err(to_program_error)?;
 
         // Copy invoke_context accounts modifications into caller's account_info
         let transaction_context = &invoke_context.transaction_context;
 
         let instruction_context = transaction_context
             .get_current_instruction_context()
-            .map_err(|err| {
-                ProgramError::try_from_custom(err).unwrap_or_else(|err| panic!("{}", err))
-            })?;
+            .map_err(to_program_error)?;
 
-        for (index_in_caller, account_info_index) in account_indices.into_iter() {
+        for (index_in_caller, index_in_transaction, account_info_index, data_before_cpi) in
+            account_indices.into_iter()
+        {
+            let data_unchanged = Arc::ptr_eq(
+                &data_before_cpi,
+                &transaction_context
+                    .get_account_at_index(index_in_transaction)
+                    .map_err(to_program_error)?
+                    .borrow()
+                    .data_clone(),
+            );
             let borrowed_account = instruction_context
                 .try_borrow_instruction_account(transaction_context, index_in_caller)
-                .map_err(|err| {
-                    ProgramError::try_from_custom(err).unwrap_or_else(|err| panic!("{}", err))
-                })?;
+                .map_err(to_program_error)?;
             let account_info = &account_infos[account_info_index];
-            **account_info.try_borrow_mut_lamports().unwrap() = borrowed_account.get_lamports();
+            **account_info
+                .try_borrow_mut_lamports()
+                .map_err(|_| ProgramError::AccountBorrowFailed)? = borrowed_account.get_lamports();
             if account_info.owner != borrowed_account.get_owner() {
-                // TODO Figure out a better way to allow the System Program to set the account owner
-                #[allow(clippy::transmute_ptr_to_ptr)]
-                #[allow(mutable_transmutes)]
-                let account_info_mut =
-                    unsafe { transmute::<&Pubkey, &mut Pubkey>(account_info.owner) };
-                *account_info_mut = *borrowed_account.get_owner();
+                // The owner points into the serialized input of the caller, which is mutable
+                // memory, so it is updated the same way as by the program through assign
+                account_info.assign(borrowed_account.get_owner());
             }
 
             let new_data = borrowed_account.get_data();
             let new_len = new_data.len();
 
-            // Resize account_info data
+            // Most callees leave the data untouched, checking the buffer does not depend on the
+            // size of the account unlike comparing the data
+            if data_unchanged {
+                continue;
+            }
+
+            // Resize account_info data, the bytes cut off by a shrink are zeroed as the runtime
+            // does, so that growing the account again does not expose the old data
+            if account_info.data_len() > new_len {
+                account_info
+                    .try_borrow_mut_data()
+                    .map_err(|_| ProgramError::AccountBorrowFailed)?[new_len..]
+                    .fill(0);
+            }
+            // The callee may grow the account past the space the caller has for it, its realloc
+            // fails with InvalidRealloc as the copy back of the runtime
             if account_info.data_len() != new_len {
-                account_info.realloc(new_len, false).unwrap();
+                account_info
+                    .realloc(new_len, false)
+                    .map_err(|_| ProgramError::InvalidRealloc)?;
             }
 
-            // Clone the data
-            let mut data = account_info.try_borrow_mut_data().unwrap();
+            // The bytes grown by the realloc are not zeroed, they are defined by copying the new
+            // data over the whole buffer, which must therefore have exactly its length
+            let mut data = account_info
+                .try_borrow_mut_data()
+                .map_err(|_| ProgramError::AccountBorrowFailed)?;
+            if data.len() != new_len {
+                return Err(internal_error(
+                    format!(
+                        "account data has {} bytes after realloc to {new_len}",
+                        data.len()
+                    ),
+                    ProgramError::InvalidRealloc,
+                ));
+            }
 
-            data.clone_from_slice(new_data);
+            data.copy_from_slice(new_data);
         }
 
-        stable_log::program_success(&log_collector, &instruction.program_id);
-
         Ok(())
     }
+
+    // The memory syscalls behave as the default stubs, charging the cost of the runtime on top
+    unsafe fn sol_memcpy(&self, dst: *mut u8, src: *const u8, n: usize) {
+        count_syscall("sol_memcpy");
+        consume_compute_units(|compute_budget| mem_op_compute_units(compute_budget, n));
+        check_memory_range("sol_memcpy", dst, n);
+        check_memory_range("sol_memcpy", src, n);
+        assert!(
+            program_stubs::is_nonoverlapping(src as usize, n, dst as usize, n),
+            "memcpy does not support overlapping regions"
+        );
+        std::ptr::copy_nonoverlapping(src, dst, n);
+    }
+    unsafe fn sol_memmove(&self, dst: *mut u8, src: *const u8, n: usize) {
+        count_syscall("sol_memmove");
+        consume_compute_units(|compute_budget| mem_op_compute_units(compute_budget, n));
+        check_memory_range("sol_memmove", dst, n);
+        check_memory_range("sol_memmove", src, n);
+        std::ptr::copy(src, dst, n);
+    }
+    unsafe fn sol_memcmp(&self, s1: *const u8, s2: *const u8, n: usize, result: *mut i32) {
+        count_syscall("sol_memcmp");
+        consume_compute_units(|compute_budget| mem_op_compute_units(compute_budget, n));
+        check_memory_range("sol_memcmp", s1, n);
+        check_memory_range("sol_memcmp", s2, n);
+        let s1 = std::slice::from_raw_parts(s1, n);
+        let s2 = std::slice::from_raw_parts(s2, n);
+        // As in the runtime the bytes are compared unsigned, 0x00 against 0xFF gives -255 on
+        // every platform
+        *result = s1
+            .iter()
+            .zip(s2)
+            .find(|(a, b)| a != b)
+            .map_or(0, |(a, b)| *a as i32 - *b as i32);
+    }
+    unsafe fn sol_memset(&self, s: *mut u8, c: u8, n: usize) {
+        count_syscall("sol_memset");
+        consume_compute_units(|compute_budget| mem_op_compute_units(compute_budget, n));
+        check_memory_range("sol_memset", s, n);
+        std::ptr::write_bytes(s, c, n);
+    }
+
     fn sol_get_return_data(
         &self,
     ) -> std::option::Option<(solana_program::pubkey::Pubkey, std::vec::Vec<u8>)> {
-        let (program_id, data) = get_invoke_context().transaction_context.get_return_data();
+        count_syscall("sol_get_return_data");
+        record_syscall(|| SyscallEvent::GetReturnData);
+        if let Some(SyscallOverride::GetReturnData(get_return_data)) =
+            get_syscall_override("sol_get_return_data").as_deref()
+        {
+            return get_return_data().map(|(program_id, data)| {
+                (
+                    solana_program::pubkey::Pubkey::new_from_array(program_id.to_bytes()),
+                    data,
+                )
+            });
+        }
+        consume_compute_units(|compute_budget| compute_budget.syscall_base_cost);
+        // The return data is shared by the whole transaction and cleared before every program
+        // runs, so a caller sees the return data of the last program which set it, e.g. B and
+        // not C in A -> B -> C if B sets it after its CPI. Like on chain, empty return data is
+        // no return data.
+        let (program_id, data) = get_return_data()?;
+
+        // The copy of the data and the program id is charged on top of the base cost
+        consume_compute_units(|compute_budget| {
+            ((data.len() + size_of::<Pubkey>()) as u64) / compute_budget.cpi_bytes_per_unit
+        });
 
-        let program_id =
-            unsafe { transmute::<&Pubkey, &solana_program::pubkey::Pubkey>(program_id) };
-        Some((*program_id, data.to_vec()))
+        Some((
+            solana_program::pubkey::Pubkey::new_from_array(program_id.to_bytes()),
+            data,
+        ))
     }
     fn sol_set_return_data(&self, data: &[u8]) {
-        let invoke_context = get_invoke_context();
-        let transaction_context = &mut invoke_context.transaction_context;
-        let instruction_context = transaction_context
-            .get_current_instruction_context()
-            .unwrap();
-        let caller = *instruction_context
-            .get_last_program_key(transaction_context)
-            .unwrap();
-        transaction_context
-            .set_return_data(caller, data.to_vec())
-            .unwrap();
+        count_syscall("sol_set_return_data");
+        record_syscall(|| SyscallEvent::SetReturnData { len: data.len() });
+        if let Some(SyscallOverride::SetReturnData(set_return_data)) =
+            get_syscall_override("sol_set_return_data").as_deref()
+        {
+            return set_return_data(data);
+        }
+        consume_compute_units(|compute_budget| {
+            compute_budget
+                .syscall_base_cost
+                .saturating_add((data.len() as u64) / compute_budget.cpi_bytes_per_unit)
+        });
+        // The return data belongs to the executing program, there is none outside of an
+        // instruction, e.g. when the stubs are called directly by the harness, or without an
+        // invoke context at all
+        if !is_invoke_context_set() {
+            return;
+        }
+        let Some(caller) = current_program_id() else {
+            return;
+        };
+        let mut data = data.to_vec();
+        if let Some(hook) = configured_return_data_hook() {
+            hook(&caller, &mut data);
+        }
+        // The syscall aborts the program on chain, with no_panic set the instruction fails once
+        // the program returns and the return data is left as is
+        if data.len() > MAX_RETURN_DATA {
+            return fail_program(internal_error(
+                format!(
+                    "return data of {} bytes exceeds MAX_RETURN_DATA of {MAX_RETURN_DATA}",
+                    data.len()
+                ),
+                ProgramError::InvalidArgument,
+            ));
+        }
+        if let Err(err) = get_invoke_context()
+            .transaction_context
+            .set_return_data(caller, data)
+        {
+            fail_program(internal_error(err, ProgramError::InvalidArgument));
+        }
+    }
+
+    fn sol_get_processed_sibling_instruction(
+        &self,
+        index: usize,
+    ) -> Option<solana_program::instruction::Instruction> {
+        count_syscall("sol_get_processed_sibling_instruction");
+        consume_compute_units(|compute_budget| compute_budget.syscall_base_cost);
+        if !is_invoke_context_set() {
+            return None;
+        }
+        let instruction = processed_sibling_instructions().into_iter().nth(index)?;
+        Some(unsafe {
+            transmute::<Instruction, solana_program::instruction::Instruction>(instruction)
+        })
     }
 
     fn sol_get_stack_height(&self) -> u64 {
-        let invoke_context = get_invoke_context();
-        invoke_context.get_stack_height().try_into().unwrap()
+        count_syscall("sol_get_stack_height");
+        record_syscall(|| SyscallEvent::GetStackHeight);
+        if let Some(SyscallOverride::GetStackHeight(get_stack_height)) =
+            get_syscall_override("sol_get_stack_height").as_deref()
+        {
+            return get_stack_height();
+        }
+        consume_compute_units(|compute_budget| compute_budget.syscall_base_cost);
+        // Outside of an instruction, e.g. when the harness calls the stubs directly, there is no
+        // instruction on the stack and the height is 0
+        if !is_invoke_context_set() {
+            return 0;
+        }
+        get_invoke_context().get_stack_height() as u64
     }
 }
 
+/// Base of the `ProgramError::Custom` codes used for `InstructionError` variants which have no
+/// `ProgramError` counterpart in solana 1.17. The code of such variant is the base plus its
+/// position in `UNMAPPED_INSTRUCTION_ERRORS`.
+pub const UNMAPPED_INSTRUCTION_ERROR_BASE: u32 = 0xFFFF_0000;
+
+/// `ProgramError::Custom` code of an `InstructionError` which could not be converted at all.
+pub const UNKNOWN_INSTRUCTION_ERROR: u32 = u32::MAX;
+
+/// `InstructionError` variants without a `ProgramError` counterpart in solana 1.17.
+/// New entries must only be appended so that the already assigned codes stay stable.
+pub const UNMAPPED_INSTRUCTION_ERRORS: [InstructionError; 30] = [
+    InstructionError::GenericError,
+    InstructionError::UnbalancedInstruction,
+    InstructionError::ModifiedProgramId,
+    InstructionError::ExternalAccountLamportSpend,
+    InstructionError::ExternalAccountDataModified,
+    InstructionError::ReadonlyLamportChange,
+    InstructionError::ReadonlyDataModified,
+    InstructionError::DuplicateAccountIndex,
+    InstructionError::ExecutableModified,
+    InstructionError::RentEpochModified,
+    InstructionError::AccountDataSizeChanged,
+    InstructionError::AccountNotExecutable,
+    InstructionError::AccountBorrowOutstanding,
+    InstructionError::DuplicateAccountOutOfSync,
+    InstructionError::InvalidError,
+    InstructionError::ExecutableDataModified,
+    InstructionError::ExecutableLamportChange,
+    InstructionError::ExecutableAccountNotRentExempt,
+    InstructionError::UnsupportedProgramId,
+    InstructionError::CallDepth,
+    InstructionError::MissingAccount,
+    InstructionError::ReentrancyNotAllowed,
+    InstructionError::ComputationalBudgetExceeded,
+    InstructionError::PrivilegeEscalation,
+    InstructionError::ProgramEnvironmentSetupFailure,
+    InstructionError::ProgramFailedToComplete,
+    InstructionError::ProgramFailedToCompile,
+    InstructionError::Immutable,
+    InstructionError::IncorrectAuthority,
+    InstructionError::MaxAccountsExceeded,
+];
+
 /// The V1 implementation is meant for solana crate version 1.17 and higher. As 1.17 is considered as
 /// completely different type compared to 2.0, we need to implement this manually.
 impl TridentTryFrom<InstructionError> for ProgramError {
@@ -317,6 +946,69 @@ impl TridentTryFrom<InstructionError> for ProgramError {
             Self::Error::AccountBorrowFailed => Ok(Self::AccountBorrowFailed),
             Self::Error::MaxSeedLengthExceeded => Ok(Self::MaxSeedLengthExceeded),
             Self::Error::InvalidSeeds => Ok(Self::InvalidSeeds),
+            // The message is a String in 1.18 and 2.2 alike and is kept as is, unlike on chain
+            // where the error is returned as a code and its message lost
+            Self::Error::BorshIoError(err) => Ok(Self::BorshIoError(err)),
+            Self::Error::AccountNotRentExempt => Ok(Self::AccountNotRentExempt),
+            Self::Error::UnsupportedSysvar => Ok(Self::UnsupportedSysvar),
+            Self::Error::IllegalOwner => Ok(Self::IllegalOwner),
+            Self::Error::MaxAccountsDataAllocationsExceeded => {
+                Ok(Self::MaxAccountsDataAllocationsExceeded)
+            }
+            Self::Error::InvalidRealloc => Ok(Self::InvalidRealloc),
+            Self::Error::MaxInstructionTraceLengthExceeded => {
+                Ok(Self::MaxInstructionTraceLengthExceeded)
+            }
+            Self::Error::BuiltinProgramsMustConsumeComputeUnits => {
+                Ok(Self::BuiltinProgramsMustConsumeComputeUnits)
+            }
+            Self::Error::InvalidAccountOwner => Ok(Self::InvalidAccountOwner),
+            Self::Error::ArithmeticOverflow => Ok(Self::ArithmeticOverflow),
+            _ => match UNMAPPED_INSTRUCTION_ERRORS
+                .iter()
+                .position(|unmapped| *unmapped == error)
+            {
+                Some(offset) => Ok(Self::Custom(
+                    UNMAPPED_INSTRUCTION_ERROR_BASE + offset as u32,
+                )),
+                None => Err(error),
+            },
+        }
+    }
+}
+
+/// Infallible variant of `ProgramError::try_from_custom`, errors without a conversion are
+/// reported as `ProgramError::Custom(UNKNOWN_INSTRUCTION_ERROR)`.
+pub fn to_program_error(error: InstructionError) -> ProgramError {
+    ProgramError::try_from_custom(error).unwrap_or(ProgramError::Custom(UNKNOWN_INSTRUCTION_ERROR))
+}
+
+/// Inverse of the `InstructionError` to `ProgramError` conversion above, `ProgramError::Custom`
+/// codes from the unmapped range are converted back to the original `InstructionError` variant.
+impl TridentTryFrom<ProgramError> for InstructionError {
+    type Error = ProgramError;
+
+    fn try_from_custom(error: ProgramError) -> Result<Self, Self::Error> {
+        match error {
+            Self::Error::Custom(err) => Ok(err
+                .checked_sub(UNMAPPED_INSTRUCTION_ERROR_BASE)
+                .and_then(|offset| UNMAPPED_INSTRUCTION_ERRORS.get(offset as usize))
+                .cloned()
+                .unwrap_or(Self::Custom(err))),
+            Self::Error::InvalidArgument => Ok(Self::InvalidArgument),
+            Self::Error::InvalidInstructionData => Ok(Self::InvalidInstructionData),
+            Self::Error::InvalidAccountData => Ok(Self::InvalidAccountData),
+            Self::Error::AccountDataTooSmall => Ok(Self::AccountDataTooSmall),
+            Self::Error::InsufficientFunds => Ok(Self::InsufficientFunds),
+            Self::Error::IncorrectProgramId => Ok(Self::IncorrectProgramId),
+            Self::Error::MissingRequiredSignature => Ok(Self::MissingRequiredSignature),
+            Self::Error::AccountAlreadyInitialized => Ok(Self::AccountAlreadyInitialized),
+            Self::Error::UninitializedAccount => Ok(Self::UninitializedAccount),
+            Self::Error::NotEnoughAccountKeys => Ok(Self::NotEnoughAccountKeys),
+            Self::Error::AccountBorrowFailed => Ok(Self::AccountBorrowFailed),
+            Self::Error::MaxSeedLengthExceeded => Ok(Self::MaxSeedLengthExceeded),
+            Self::Error::InvalidSeeds => Ok(Self::InvalidSeeds),
+            // The message is kept as in the conversion above
             Self::Error::BorshIoError(err) => Ok(Self::BorshIoError(err)),
             Self::Error::AccountNotRentExempt => Ok(Self::AccountNotRentExempt),
             Self::Error::UnsupportedSysvar => Ok(Self::UnsupportedSysvar),
@@ -333,7 +1025,120 @@ impl TridentTryFrom<InstructionError> for ProgramError {
             }
             Self::Error::InvalidAccountOwner => Ok(Self::InvalidAccountOwner),
             Self::Error::ArithmeticOverflow => Ok(Self::ArithmeticOverflow),
-            _ => Err(error),
         }
     }
 }
+
+/// Error of the instruction at the given index of a transaction, as reported to clients. The
+/// index is returned back with the error if it does not fit the `u8` of `TransactionError`.
+impl TridentTryFrom<(usize, InstructionError)> for TransactionError {
+    type Error = (usize, InstructionError);
+
+    fn try_from_custom(
+        (instruction_index, error): (usize, InstructionError),
+    ) -> Result<Self, Self::Error> {
+        match u8::try_from(instruction_index) {
+            Ok(index) => Ok(Self::InstructionError(index, error)),
+            Err(_) => Err((instruction_index, error)),
+        }
+    }
+}
+
+/// Account meta of solana_program 1.18 rebuilt field by field, for harness code constructing
+/// instructions for the runtime without relying on the layouts matching.
+impl TridentTryFrom<&solana_program::instruction::AccountMeta> for AccountMeta {
+    type Error = Infallible;
+
+    fn try_from_custom(
+        account_meta: &solana_program::instruction::AccountMeta,
+    ) -> Result<Self, Self::Error> {
+        Ok(Self {
+            pubkey: Pubkey::new_from_array(account_meta.pubkey.to_bytes()),
+            is_signer: account_meta.is_signer,
+            is_writable: account_meta.is_writable,
+        })
+    }
+}
+
+impl TridentTryFrom<&AccountMeta> for solana_program::instruction::AccountMeta {
+    type Error = Infallible;
+
+    fn try_from_custom(account_meta: &AccountMeta) -> Result<Self, Self::Error> {
+        Ok(Self {
+            pubkey: solana_program::pubkey::Pubkey::new_from_array(account_meta.pubkey.to_bytes()),
+            is_signer: account_meta.is_signer,
+            is_writable: account_meta.is_writable,
+        })
+    }
+}
+
+/// Instruction of solana_program 1.18 rebuilt field by field, see the conversion of `AccountMeta`.
+impl TridentTryFrom<&solana_program::instruction::Instruction> for Instruction {
+    type Error = Infallible;
+
+    fn try_from_custom(
+        instruction: &solana_program::instruction::Instruction,
+    ) -> Result<Self, Self::Error> {
+        Ok(Self {
+            program_id: Pubkey::new_from_array(instruction.program_id.to_bytes()),
+            accounts: instruction
+                .accounts
+                .iter()
+                .map(AccountMeta::try_from_custom)
+                .collect::<Result<_, _>>()?,
+            data: instruction.data.clone(),
+        })
+    }
+}
+
+impl TridentTryFrom<&Instruction> for solana_program::instruction::Instruction {
+    type Error = Infallible;
+
+    fn try_from_custom(instruction: &Instruction) -> Result<Self, Self::Error> {
+        Ok(Self {
+            program_id: solana_program::pubkey::Pubkey::new_from_array(
+                instruction.program_id.to_bytes(),
+            ),
+            accounts: instruction
+                .accounts
+                .iter()
+                .map(solana_program::instruction::AccountMeta::try_from_custom)
+                .collect::<Result<_, _>>()?,
+            data: instruction.data.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transmuted_account_infos_read_the_same_fields() {
+        let key = solana_program::pubkey::Pubkey::new_from_array([3; 32]);
+        let owner = solana_program::pubkey::Pubkey::new_from_array([4; 32]);
+        let mut lamports = 5;
+        let mut data = [6, 7];
+        let account_infos = [solana_program::account_info::AccountInfo::new(
+            &key,
+            true,
+            false,
+            &mut lamports,
+            &mut data,
+            &owner,
+            true,
+            8,
+        )];
+
+        let sdk_account_infos: &[AccountInfo] = unsafe { transmute(&account_infos[..]) };
+        let account_info = &sdk_account_infos[0];
+        assert_eq!(account_info.key.to_bytes(), [3; 32]);
+        assert_eq!(account_info.owner.to_bytes(), [4; 32]);
+        assert_eq!(account_info.lamports(), 5);
+        assert_eq!(*account_info.data.borrow(), &[6, 7]);
+        assert!(account_info.is_signer);
+        assert!(!account_info.is_writable);
+        assert!(account_info.executable);
+        assert_eq!(account_info.rent_epoch, 8);
+    }
+}
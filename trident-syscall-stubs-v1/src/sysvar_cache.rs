@@ -0,0 +1,277 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use solana_sdk::account::AccountSharedData;
+use solana_sdk::account::ReadableAccount;
+use solana_sdk::clock::Clock;
+use solana_sdk::epoch_rewards::EpochRewards;
+use solana_sdk::epoch_schedule::EpochSchedule;
+#[allow(deprecated)]
+use solana_sdk::fee_calculator::FeeCalculator;
+use solana_sdk::hash::Hash;
+use solana_sdk::instruction::InstructionError;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::rent::Rent;
+use solana_sdk::slot_hashes::SlotHashes;
+use solana_sdk::stake_history::StakeHistory;
+#[allow(deprecated)]
+use solana_sdk::sysvar::fees::Fees;
+use solana_sdk::sysvar::last_restart_slot::LastRestartSlot;
+use solana_sdk::sysvar::SysvarId;
+
+pub use solana_program_runtime::sysvar_cache::SysvarCache;
+
+use crate::get_invoke_context;
+use crate::is_invoke_context_set;
+
+thread_local! {
+    static SYSVAR_OVERRIDES: RefCell<SysvarCache> = RefCell::new(SysvarCache::default());
+    /// Sysvars whose account given to `set_sysvars_from_accounts` could not be deserialized.
+    static MALFORMED_SYSVARS: RefCell<HashSet<Pubkey>> = RefCell::new(HashSet::new());
+}
+
+/// Builder of the `SysvarCache` handed over to the `InvokeContext`, the sysvars set here are
+/// returned by the `sol_get_*_sysvar` stubs once the context is installed.
+#[derive(Default)]
+pub struct SysvarCacheBuilder {
+    sysvar_cache: SysvarCache,
+}
+
+impl SysvarCacheBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn clock(mut self, clock: &Clock) -> Self {
+        self.sysvar_cache.set_sysvar_for_tests(clock);
+        self
+    }
+
+    pub fn rent(mut self, rent: &Rent) -> Self {
+        self.sysvar_cache.set_sysvar_for_tests(rent);
+        self
+    }
+
+    /// Set the Rent with the given parameters, for programs checking rent exemption against
+    /// values other than the defaults. See `minimum_balance` for the resulting threshold.
+    pub fn rent_parameters(
+        self,
+        lamports_per_byte_year: u64,
+        exemption_threshold: f64,
+        burn_percent: u8,
+    ) -> Self {
+        self.rent(&Rent {
+            lamports_per_byte_year,
+            exemption_threshold,
+            burn_percent,
+        })
+    }
+
+    pub fn epoch_schedule(mut self, epoch_schedule: &EpochSchedule) -> Self {
+        self.sysvar_cache.set_sysvar_for_tests(epoch_schedule);
+        self
+    }
+
+    pub fn epoch_rewards(mut self, epoch_rewards: &EpochRewards) -> Self {
+        self.sysvar_cache.set_sysvar_for_tests(epoch_rewards);
+        self
+    }
+
+    /// Set whether the epoch rewards distribution is active, on the EpochRewards set before or
+    /// the default one. An active distribution has at least one partition, an inactive one is
+    /// absent for the programs and `sol_get_epoch_rewards_sysvar` fails with `UNSUPPORTED_SYSVAR`.
+    pub fn epoch_rewards_active(mut self, active: bool) -> Self {
+        let mut epoch_rewards = self
+            .sysvar_cache
+            .get_epoch_rewards()
+            .map(|epoch_rewards| EpochRewards::clone(&epoch_rewards))
+            .unwrap_or_default();
+        epoch_rewards.active = active;
+        if active {
+            epoch_rewards.num_partitions = epoch_rewards.num_partitions.max(1);
+        }
+        self.sysvar_cache.set_sysvar_for_tests(&epoch_rewards);
+        self
+    }
+
+    /// Seed the deprecated Fees sysvar, for legacy programs still reading it.
+    #[allow(deprecated)]
+    pub fn fees(mut self, fees: &Fees) -> Self {
+        self.sysvar_cache.set_sysvar_for_tests(fees);
+        self
+    }
+
+    /// Seed the deprecated Fees sysvar with the given fee per signature.
+    #[allow(deprecated)]
+    pub fn lamports_per_signature(self, lamports_per_signature: u64) -> Self {
+        self.fees(&Fees {
+            fee_calculator: FeeCalculator::new(lamports_per_signature),
+        })
+    }
+
+    /// Seed the LastRestartSlot read by `sol_get_last_restart_slot`, a default cache has none
+    /// and the syscall fails with `UNSUPPORTED_SYSVAR`.
+    pub fn last_restart_slot(mut self, last_restart_slot: &LastRestartSlot) -> Self {
+        self.sysvar_cache.set_sysvar_for_tests(last_restart_slot);
+        self
+    }
+
+    /// Deserialize the sysvars among the given accounts, e.g. a dump of the sysvar accounts of
+    /// a cluster. Accounts which are not sysvars are ignored, sysvars set before are kept.
+    pub fn accounts(mut self, accounts: &[(Pubkey, AccountSharedData)]) -> Self {
+        self.sysvar_cache
+            .fill_missing_entries(|pubkey, set_sysvar| {
+                if let Some((_, account)) = accounts.iter().find(|(key, _)| key == pubkey) {
+                    set_sysvar(account.data());
+                }
+            });
+        self
+    }
+
+    pub fn build(self) -> SysvarCache {
+        self.sysvar_cache
+    }
+}
+
+/// Read a sysvar from the overrides of this thread, falling back to the sysvar cache of the
+/// current invoke context if there is one. A sysvar overridden with a malformed account fails
+/// with `InvalidAccountData`.
+pub(crate) fn read_sysvar<T: SysvarId>(
+    get: impl Fn(&SysvarCache) -> Result<Arc<T>, InstructionError>,
+) -> Result<Arc<T>, InstructionError> {
+    SYSVAR_OVERRIDES
+        .with(|overrides| get(&overrides.borrow()))
+        .or_else(|err| {
+            if MALFORMED_SYSVARS.with(|malformed| malformed.borrow().contains(&T::id())) {
+                return Err(InstructionError::InvalidAccountData);
+            }
+            if !is_invoke_context_set() {
+                return Err(err);
+            }
+            get(get_invoke_context().get_sysvar_cache())
+        })
+}
+
+/// Override the Clock returned by `sol_get_clock_sysvar`, the override is kept across invoke
+/// contexts until `clear_sysvar_overrides` is called.
+pub fn set_clock(clock: &Clock) {
+    SYSVAR_OVERRIDES.with(|overrides| overrides.borrow_mut().set_sysvar_for_tests(clock));
+}
+
+/// Override the Rent returned by `sol_get_rent_sysvar`, see `set_clock`.
+pub fn set_rent(rent: &Rent) {
+    SYSVAR_OVERRIDES.with(|overrides| overrides.borrow_mut().set_sysvar_for_tests(rent));
+}
+
+/// Override the EpochSchedule returned by `sol_get_epoch_schedule_sysvar`, see `set_clock`.
+pub fn set_epoch_schedule(epoch_schedule: &EpochSchedule) {
+    SYSVAR_OVERRIDES.with(|overrides| overrides.borrow_mut().set_sysvar_for_tests(epoch_schedule));
+}
+
+/// Override the LastRestartSlot returned by `sol_get_last_restart_slot`, see `set_clock`.
+pub fn set_last_restart_slot(last_restart_slot: &LastRestartSlot) {
+    SYSVAR_OVERRIDES.with(|overrides| {
+        overrides
+            .borrow_mut()
+            .set_sysvar_for_tests(last_restart_slot)
+    });
+}
+
+/// Override the sysvars among the given accounts, see `SysvarCacheBuilder::accounts`. The
+/// sysvars overridden before and missing from the accounts are kept. The syscall of a sysvar
+/// whose account does not deserialize fails with `InvalidAccountData` rather than reporting a
+/// missing sysvar, until the sysvar is overridden again or the overrides are cleared.
+pub fn set_sysvars_from_accounts(accounts: &[(Pubkey, AccountSharedData)]) {
+    SYSVAR_OVERRIDES.with(|overrides| {
+        let mut overrides = overrides.borrow_mut();
+        let mut sysvar_cache = SysvarCacheBuilder::new().accounts(accounts).build();
+        let malformed = MALFORMED_SYSVARS.with(|malformed| {
+            let mut malformed = malformed.borrow_mut();
+            for (pubkey, _) in accounts
+                .iter()
+                .filter(|(pubkey, _)| is_cached_sysvar(pubkey))
+            {
+                if sysvar_cache.sysvar_id_to_buffer(pubkey).is_some() {
+                    malformed.remove(pubkey);
+                } else {
+                    malformed.insert(*pubkey);
+                }
+            }
+            malformed.clone()
+        });
+        sysvar_cache.fill_missing_entries(|pubkey, set_sysvar| {
+            if malformed.contains(pubkey) {
+                return;
+            }
+            if let Some(data) = overrides.sysvar_id_to_buffer(pubkey) {
+                set_sysvar(data);
+            }
+        });
+        // Fees has no buffer, it is only kept as an object
+        #[allow(deprecated)]
+        if let (Err(_), Ok(fees)) = (sysvar_cache.get_fees(), overrides.get_fees()) {
+            sysvar_cache.set_sysvar_for_tests(&*fees);
+        }
+        *overrides = sysvar_cache;
+    });
+}
+
+/// Minimum balance for an account with the given data length to be rent exempt, as computed by
+/// `Rent::minimum_balance` from the Rent returned by `sol_get_rent_sysvar`, or from the default
+/// Rent when none is set.
+pub fn minimum_balance(data_len: usize) -> u64 {
+    read_sysvar(SysvarCache::get_rent)
+        .map(|rent| rent.minimum_balance(data_len))
+        .unwrap_or_else(|_| Rent::default().minimum_balance(data_len))
+}
+
+/// Advance the slot of the current Clock by the given number of slots.
+pub fn advance_slot(slots: u64) {
+    let mut clock = read_sysvar(SysvarCache::get_clock)
+        .map(|clock| Clock::clone(&clock))
+        .unwrap_or_default();
+    clock.slot = clock.slot.saturating_add(slots);
+    set_clock(&clock);
+}
+
+/// Warp the Clock to the given slot, with the epoch and leader schedule epoch derived from the
+/// current EpochSchedule. With a hash the slot is also recorded in SlotHashes.
+pub fn warp_to_slot(slot: u64, slot_hash: Option<Hash>) {
+    let epoch_schedule = read_sysvar(SysvarCache::get_epoch_schedule)
+        .map(|epoch_schedule| EpochSchedule::clone(&epoch_schedule))
+        .unwrap_or_default();
+    let mut clock = read_sysvar(SysvarCache::get_clock)
+        .map(|clock| Clock::clone(&clock))
+        .unwrap_or_default();
+    clock.slot = slot;
+    clock.epoch = epoch_schedule.get_epoch(slot);
+    clock.leader_schedule_epoch = epoch_schedule.get_leader_schedule_epoch(slot);
+    set_clock(&clock);
+
+    if let Some(slot_hash) = slot_hash {
+        let mut slot_hashes = read_sysvar(SysvarCache::get_slot_hashes)
+            .map(|slot_hashes| SlotHashes::new(slot_hashes.slot_hashes()))
+            .unwrap_or_default();
+        slot_hashes.add(slot, slot_hash);
+        SYSVAR_OVERRIDES
+            .with(|overrides| overrides.borrow_mut().set_sysvar_for_tests(&slot_hashes));
+    }
+}
+
+pub fn clear_sysvar_overrides() {
+    SYSVAR_OVERRIDES.with(|overrides| overrides.borrow_mut().reset());
+    MALFORMED_SYSVARS.with(|malformed| malformed.borrow_mut().clear());
+}
+
+/// Whether the sysvar cache keeps the account data of the sysvar, the other sysvars are ignored
+/// by `SysvarCacheBuilder::accounts`.
+fn is_cached_sysvar(pubkey: &Pubkey) -> bool {
+    Clock::check_id(pubkey)
+        || EpochSchedule::check_id(pubkey)
+        || EpochRewards::check_id(pubkey)
+        || Rent::check_id(pubkey)
+        || SlotHashes::check_id(pubkey)
+        || StakeHistory::check_id(pubkey)
+        || LastRestartSlot::check_id(pubkey)
+}
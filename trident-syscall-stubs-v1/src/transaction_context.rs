@@ -0,0 +1,53 @@
+use solana_sdk::account::AccountSharedData;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::rent::Rent;
+use solana_sdk::transaction_context::TransactionAccount;
+use solana_sdk::transaction_context::TransactionContext;
+
+use crate::configured_compute_budget;
+use crate::executable_checks_enabled;
+
+/// Builder of the `TransactionContext` an `InvokeContext` is created with. The instruction stack
+/// depth, the trace length and the executable checks are those of the `StubConfig`, as for the
+/// instructions run by `process_one_instruction`.
+#[derive(Default)]
+pub struct TransactionContextBuilder {
+    accounts: Vec<TransactionAccount>,
+    rent: Rent,
+}
+
+impl TransactionContextBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the accounts after those added before, the index of an account in the transaction
+    /// is its position among all added accounts.
+    pub fn accounts(mut self, accounts: &[(Pubkey, AccountSharedData)]) -> Self {
+        self.accounts.extend_from_slice(accounts);
+        self
+    }
+
+    pub fn account(mut self, pubkey: &Pubkey, account: &AccountSharedData) -> Self {
+        self.accounts.push((*pubkey, account.clone()));
+        self
+    }
+
+    pub fn rent(mut self, rent: &Rent) -> Self {
+        self.rent = rent.clone();
+        self
+    }
+
+    pub fn build(self) -> TransactionContext {
+        let compute_budget = configured_compute_budget();
+        let mut transaction_context = TransactionContext::new(
+            self.accounts,
+            self.rent,
+            compute_budget.max_instruction_stack_depth,
+            compute_budget.max_instruction_trace_length,
+        );
+        transaction_context
+            .set_remove_accounts_executable_flag_checks(!executable_checks_enabled());
+        transaction_context
+    }
+}
@@ -0,0 +1,54 @@
+mod common;
+
+use solana_program::account_info::AccountInfo;
+use solana_program::entrypoint::ProgramResult;
+use solana_program::instruction::AccountMeta as ProgramAccountMeta;
+use solana_program::instruction::Instruction as ProgramInstruction;
+use solana_sdk::account::AccountSharedData;
+use solana_sdk::account::ReadableAccount;
+
+use common::*;
+use trident_syscall_stubs_v1::*;
+
+/// Has `CALLEE` reallocate the account to 40 bytes through the account infos of an
+/// `AccountInfoStorage` instead of its own, then makes the same change to its own account info.
+fn invoke_with_stored_accounts(
+    _program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    _data: &[u8],
+) -> ProgramResult {
+    let mut storage =
+        AccountInfoStorage::new(&common::accounts(AccountSharedData::new(5, 4, &CALLEE)));
+    {
+        let account_infos = storage.account_infos();
+        let instruction = ProgramInstruction {
+            program_id: *account_infos[1].key,
+            accounts: vec![ProgramAccountMeta::new(*account_infos[0].key, false)],
+            data: vec![REALLOC, 40],
+        };
+        solana_program::program::invoke(&instruction, &account_infos)?;
+        assert_eq!(account_infos[0].data_len(), 40);
+    }
+
+    let stored_accounts = storage.accounts();
+    assert_eq!(stored_accounts[0].0, ACCOUNT);
+    assert_eq!(stored_accounts[0].1.data(), &[0; 40]);
+    assert_eq!(stored_accounts[0].1.owner(), &CALLEE);
+
+    accounts[0].realloc(40, true)
+}
+
+#[test]
+fn stored_account_infos_can_be_passed_to_a_cpi() {
+    set_stubs_v1();
+    set_program_entrypoint(&CALLEE, test_program);
+
+    let processed = process_one_instruction(
+        &CALLER,
+        invoke_with_stored_accounts,
+        &accounts(AccountSharedData::new(5, 4, &CALLEE)),
+        &[],
+    );
+    assert_eq!(processed.result, Ok(()));
+    assert_eq!(processed.accounts[0].1.data(), &[0; 40]);
+}
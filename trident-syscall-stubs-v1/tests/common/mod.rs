@@ -0,0 +1,92 @@
+#![allow(dead_code)]
+use solana_program::account_info::AccountInfo;
+use solana_program::entrypoint::ProgramResult;
+use solana_program::instruction::AccountMeta as ProgramAccountMeta;
+use solana_program::instruction::Instruction as ProgramInstruction;
+use solana_program::msg;
+use solana_program::program_error::ProgramError;
+use solana_sdk::account::AccountSharedData;
+use solana_sdk::account::WritableAccount;
+use solana_sdk::bpf_loader;
+use solana_sdk::instruction::AccountMeta;
+use solana_sdk::pubkey::Pubkey;
+
+/// Program run at the top level.
+pub const CALLER: Pubkey = Pubkey::new_from_array([1; 32]);
+/// Program invoked by the caller.
+pub const CALLEE: Pubkey = Pubkey::new_from_array([2; 32]);
+/// Account passed to both programs.
+pub const ACCOUNT: Pubkey = Pubkey::new_from_array([9; 32]);
+
+/// Operations of `test_program`, selected by the first byte of the instruction data.
+pub const WRITE: u8 = 0;
+pub const INVOKE: u8 = 1;
+pub const FAIL: u8 = 2;
+pub const REALLOC: u8 = 3;
+pub const SET_RETURN_DATA: u8 = 4;
+pub const ADD_LAMPORT: u8 = 5;
+
+/// The error returned by `FAIL`.
+pub const FAILURE: u32 = 7;
+
+pub fn program_account() -> AccountSharedData {
+    let mut account = AccountSharedData::new(1, 0, &bpf_loader::id());
+    #[allow(deprecated)]
+    account.set_executable(true);
+    account
+}
+
+/// Logs its program id and data, then:
+/// - `WRITE`: writes `data[1]` to the first byte of the first account
+/// - `INVOKE`: invokes the program of the second account with the rest of the data and the
+///   first account as a writable account
+/// - `FAIL`: fails with `Custom(FAILURE)`
+/// - `REALLOC`: reallocates the first account to `data[1]` bytes
+/// - `SET_RETURN_DATA`: sets the rest of the data as the return data
+/// - `ADD_LAMPORT`: adds a lamport to the first account
+pub fn test_program(
+    program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    msg!("enter {} {:?}", program_id, data);
+    match data[0] {
+        WRITE => accounts[0].try_borrow_mut_data()?[0] = data[1],
+        INVOKE => {
+            let instruction = ProgramInstruction {
+                program_id: *accounts[1].key,
+                accounts: vec![
+                    ProgramAccountMeta::new(*accounts[0].key, false),
+                    ProgramAccountMeta::new_readonly(*accounts[1].key, false),
+                ],
+                data: data[1..].to_vec(),
+            };
+            solana_program::program::invoke(&instruction, accounts)?;
+        }
+        FAIL => return Err(ProgramError::Custom(FAILURE)),
+        REALLOC => accounts[0].realloc(data[1] as usize, true)?,
+        SET_RETURN_DATA => solana_program::program::set_return_data(&data[1..]),
+        ADD_LAMPORT => **accounts[0].try_borrow_mut_lamports()? += 1,
+        _ => {}
+    }
+    Ok(())
+}
+
+/// `ACCOUNT` as a writable signer followed by the program account of `program_id`.
+pub fn accounts_with_program(
+    account: AccountSharedData,
+    program_id: &Pubkey,
+) -> Vec<(AccountMeta, AccountSharedData)> {
+    vec![
+        (AccountMeta::new(ACCOUNT, true), account),
+        (
+            AccountMeta::new_readonly(*program_id, false),
+            program_account(),
+        ),
+    ]
+}
+
+/// `ACCOUNT` as a writable signer followed by the program account of `CALLEE`.
+pub fn accounts(account: AccountSharedData) -> Vec<(AccountMeta, AccountSharedData)> {
+    accounts_with_program(account, &CALLEE)
+}
@@ -0,0 +1,258 @@
+mod common;
+
+use std::cell::Cell;
+use std::mem::size_of;
+
+use solana_compute_budget::compute_budget::ComputeBudget;
+use solana_program::account_info::AccountInfo;
+use solana_program::entrypoint::ProgramResult;
+use solana_program::sysvar::Sysvar;
+use solana_program_runtime::with_mock_invoke_context;
+use solana_sdk::account::AccountSharedData;
+use solana_sdk::instruction::InstructionError;
+
+use common::*;
+use trident_syscall_stubs_v1::*;
+
+#[test]
+fn set_compute_budget_seeds_the_meter() {
+    with_mock_invoke_context!(invoke_context, transaction_context, vec![]);
+    let _guard = set_thread_context(&mut invoke_context);
+    set_stubs_v1_with_config(StubConfig::new().charge_compute_units(true));
+
+    set_compute_budget(117);
+    // The syscall is charged before it reads the meter
+    assert_eq!(
+        solana_program::compute_units::sol_remaining_compute_units(),
+        17
+    );
+}
+
+#[test]
+fn compute_units_are_only_charged_when_configured() {
+    with_mock_invoke_context!(invoke_context, transaction_context, vec![]);
+    let _guard = set_thread_context(&mut invoke_context);
+    set_stubs_v1();
+
+    set_compute_budget(117);
+    assert_eq!(
+        solana_program::compute_units::sol_remaining_compute_units(),
+        117
+    );
+}
+
+#[test]
+fn cpi_fails_once_the_budget_is_exhausted() {
+    set_stubs_v1_with_config(StubConfig::new().charge_compute_units(true).compute_budget(
+        ComputeBudget {
+            compute_unit_limit: 500,
+            ..ComputeBudget::default()
+        },
+    ));
+    set_program_entrypoint(&CALLEE, test_program);
+
+    let processed = process_one_instruction(
+        &CALLER,
+        test_program,
+        &accounts(AccountSharedData::new(5, 4, &CALLEE)),
+        &[INVOKE, WRITE, 1],
+    );
+    assert_eq!(
+        processed.result,
+        Err(InstructionError::ComputationalBudgetExceeded)
+    );
+}
+
+/// Makes a CPI and checks that the meter only moves between consumed and remaining units.
+fn assert_consumed_and_remaining(
+    program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    _data: &[u8],
+) -> ProgramResult {
+    let budget = consumed_compute_units() + remaining_compute_units();
+    let consumed = consumed_compute_units();
+    test_program(program_id, accounts, &[INVOKE, WRITE, 1])?;
+    assert!(consumed_compute_units() > consumed);
+    assert_eq!(consumed_compute_units() + remaining_compute_units(), budget);
+    Ok(())
+}
+
+#[test]
+fn consumed_and_remaining_compute_units_add_up_to_the_budget() {
+    set_stubs_v1_with_config(StubConfig::new().charge_compute_units(true).compute_budget(
+        ComputeBudget {
+            compute_unit_limit: 200_000,
+            ..ComputeBudget::default()
+        },
+    ));
+    set_program_entrypoint(&CALLEE, test_program);
+
+    let processed = process_one_instruction(
+        &CALLER,
+        |program_id, accounts, data| {
+            assert_eq!(
+                consumed_compute_units() + remaining_compute_units(),
+                200_000
+            );
+            assert_consumed_and_remaining(program_id, accounts, data)
+        },
+        &accounts(AccountSharedData::new(5, 4, &CALLEE)),
+        &[],
+    );
+    assert_eq!(processed.result, Ok(()));
+
+    // A seeded meter is the budget
+    let processed = process_one_instruction(
+        &CALLER,
+        |program_id, accounts, data| {
+            set_compute_budget(10_000);
+            assert_eq!(consumed_compute_units(), 0);
+            assert_consumed_and_remaining(program_id, accounts, data)?;
+            assert_eq!(consumed_compute_units() + remaining_compute_units(), 10_000);
+            Ok(())
+        },
+        &accounts(AccountSharedData::new(5, 4, &CALLEE)),
+        &[],
+    );
+    assert_eq!(processed.result, Ok(()));
+
+    // The seed does not outlive its invoke context
+    let processed = process_one_instruction(
+        &CALLER,
+        |_, _, _| {
+            assert_eq!(
+                consumed_compute_units() + remaining_compute_units(),
+                200_000
+            );
+            Ok(())
+        },
+        &accounts(AccountSharedData::new(5, 4, &CALLEE)),
+        &[],
+    );
+    assert_eq!(processed.result, Ok(()));
+}
+
+#[test]
+fn installing_the_same_context_again_keeps_the_seeded_budget() {
+    with_mock_invoke_context!(invoke_context, transaction_context, vec![]);
+    let _guard = set_thread_context(&mut invoke_context);
+    set_stubs_v1_with_config(StubConfig::new().charge_compute_units(true));
+    set_compute_budget(10_000);
+    solana_program::log::sol_log("short");
+    assert_eq!(consumed_compute_units(), 100);
+
+    set_invoke_context(&mut invoke_context);
+    assert_eq!(consumed_compute_units(), 100);
+    {
+        let _guard = set_thread_context(&mut invoke_context);
+        assert_eq!(consumed_compute_units(), 100);
+    }
+    assert_eq!(consumed_compute_units(), 100);
+}
+
+#[test]
+fn syscalls_charge_their_runtime_cost() {
+    with_mock_invoke_context!(invoke_context, transaction_context, vec![]);
+    let _guard = set_thread_context(&mut invoke_context);
+    set_stubs_v1_with_config(StubConfig::new().charge_compute_units(true));
+    set_compute_budget(100_000);
+
+    // The base cost, or a unit per byte of long messages
+    let remaining = solana_program::compute_units::sol_remaining_compute_units();
+    for _ in 0..50 {
+        solana_program::log::sol_log("short");
+    }
+    for _ in 0..10 {
+        solana_program::log::sol_log(&"x".repeat(300));
+    }
+    assert_eq!(
+        remaining - solana_program::compute_units::sol_remaining_compute_units(),
+        50 * 100 + 10 * 300 + 100
+    );
+
+    let remaining = solana_program::compute_units::sol_remaining_compute_units();
+    let mut destination = [0u8; 1000];
+    solana_program::program_memory::sol_memcpy(&mut destination, &[1; 1000], 1000);
+    assert_eq!(
+        remaining - solana_program::compute_units::sol_remaining_compute_units(),
+        10 + 100
+    );
+}
+
+thread_local! {
+    static COMPLETED_CPIS: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Makes CPIs with 500 bytes of data until the budget is exhausted, checking the cost of each.
+fn invoke_until_exhausted(
+    _program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    _data: &[u8],
+) -> ProgramResult {
+    let instruction = solana_program::instruction::Instruction {
+        program_id: *accounts[1].key,
+        accounts: vec![solana_program::instruction::AccountMeta::new(
+            *accounts[0].key,
+            false,
+        )],
+        data: vec![u8::MAX; 500],
+    };
+    loop {
+        let remaining = remaining_compute_units();
+        solana_program::program::invoke(&instruction, accounts)?;
+        // The invocation cost, a unit per 250 bytes of instruction data and account data, and
+        // the units the entrypoint of the callee is charged
+        assert_eq!(remaining - remaining_compute_units(), 1_000 + 2 + 4 + 1);
+        COMPLETED_CPIS.with(|completed_cpis| completed_cpis.set(completed_cpis.get() + 1));
+    }
+}
+
+fn noop(
+    _program_id: &solana_program::pubkey::Pubkey,
+    _accounts: &[AccountInfo],
+    _data: &[u8],
+) -> ProgramResult {
+    Ok(())
+}
+
+#[test]
+fn cpis_charge_their_invocation_cost() {
+    set_stubs_v1_with_config(StubConfig::new().charge_compute_units(true).compute_budget(
+        ComputeBudget {
+            compute_unit_limit: 10_000,
+            ..ComputeBudget::default()
+        },
+    ));
+    set_program_entrypoint(&CALLEE, noop);
+
+    let processed = process_one_instruction(
+        &CALLER,
+        invoke_until_exhausted,
+        &accounts(AccountSharedData::new(5, 1_000, &CALLEE)),
+        &[],
+    );
+    assert_eq!(
+        processed.result,
+        Err(InstructionError::ComputationalBudgetExceeded)
+    );
+    assert_eq!(COMPLETED_CPIS.with(Cell::get), 9);
+}
+
+#[test]
+fn sysvar_syscalls_charge_their_base_cost_and_size() {
+    with_mock_invoke_context!(invoke_context, transaction_context, vec![]);
+    let _guard = set_thread_context(&mut invoke_context);
+    set_stubs_v1_with_config(StubConfig::new().charge_compute_units(true));
+    set_clock(&Default::default());
+    set_rent(&Default::default());
+    set_compute_budget(10_000);
+
+    solana_program::clock::Clock::get().unwrap();
+    let clock_cost = 100 + size_of::<solana_program::clock::Clock>() as u64;
+    assert_eq!(remaining_compute_units(), 10_000 - clock_cost);
+    solana_program::rent::Rent::get().unwrap();
+    let rent_cost = 100 + size_of::<solana_program::rent::Rent>() as u64;
+    assert_eq!(remaining_compute_units(), 10_000 - clock_cost - rent_cost);
+
+    clear_sysvar_overrides();
+}
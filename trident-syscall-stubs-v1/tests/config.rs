@@ -0,0 +1,23 @@
+use solana_program::msg;
+use solana_program_runtime::with_mock_invoke_context;
+
+use trident_syscall_stubs_v1::*;
+
+#[test]
+fn capture_logs_takes_effect() {
+    with_mock_invoke_context!(invoke_context, transaction_context, vec![]);
+    let _guard = set_thread_context(&mut invoke_context);
+
+    set_stubs_v1_with_config(StubConfig::new().capture_logs(false));
+    msg!("not captured");
+    assert!(take_logs().is_empty());
+
+    set_stubs_v1_with_config(StubConfig::new().capture_logs(true));
+    msg!("captured");
+    assert_eq!(take_logs(), vec!["Program log: captured"]);
+
+    // The default captures the logs as set_stubs_v1 always did
+    set_stubs_v1_with_config(StubConfig::default());
+    msg!("captured");
+    assert_eq!(take_logs(), vec!["Program log: captured"]);
+}
@@ -0,0 +1,34 @@
+use solana_sdk::instruction::AccountMeta;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+
+use trident_syscall_stubs_v1::*;
+
+#[test]
+fn instructions_round_trip_between_the_crates() {
+    let instruction = Instruction::new_with_bytes(
+        Pubkey::new_from_array([1; 32]),
+        &[1, 2, 3],
+        vec![
+            AccountMeta::new(Pubkey::new_from_array([2; 32]), true),
+            AccountMeta::new_readonly(Pubkey::new_from_array([3; 32]), false),
+            AccountMeta::new(Pubkey::new_from_array([4; 32]), false),
+        ],
+    );
+
+    let program_instruction =
+        solana_program::instruction::Instruction::try_from_custom(&instruction).unwrap();
+    assert_eq!(program_instruction.program_id.to_bytes(), [1; 32]);
+    assert_eq!(program_instruction.data, vec![1, 2, 3]);
+    assert_eq!(program_instruction.accounts.len(), 3);
+    assert_eq!(program_instruction.accounts[1].pubkey.to_bytes(), [3; 32]);
+    assert!(program_instruction.accounts[0].is_signer);
+    assert!(program_instruction.accounts[0].is_writable);
+    assert!(!program_instruction.accounts[1].is_writable);
+    assert!(!program_instruction.accounts[2].is_signer);
+
+    assert_eq!(
+        Instruction::try_from_custom(&program_instruction),
+        Ok(instruction)
+    );
+}
@@ -0,0 +1,1304 @@
+mod common;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use solana_program::account_info::AccountInfo;
+use solana_program::entrypoint::ProgramResult;
+use solana_program::instruction::AccountMeta as ProgramAccountMeta;
+use solana_program::instruction::Instruction as ProgramInstruction;
+use solana_program::program::MAX_RETURN_DATA;
+use solana_program::program_error::ProgramError;
+use solana_sdk::account::AccountSharedData;
+use solana_sdk::account::ReadableAccount;
+use solana_sdk::account::WritableAccount;
+use solana_sdk::instruction::AccountMeta;
+use solana_sdk::instruction::InstructionError;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::system_program;
+
+use common::*;
+use trident_syscall_stubs_v1::*;
+
+const PDA_SEED: &[u8] = b"pda";
+
+fn pda() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[PDA_SEED], &CALLER)
+}
+
+/// Invokes the program of the second account with the first account either as writable
+/// (`data[0] == 0`) or as signer, signing for the PDA of the caller when `data[0] == 2`.
+fn escalate(
+    _program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let account_meta = match data[0] {
+        0 => ProgramAccountMeta::new(*accounts[0].key, false),
+        _ => ProgramAccountMeta::new_readonly(*accounts[0].key, true),
+    };
+    let instruction = ProgramInstruction {
+        program_id: *accounts[1].key,
+        accounts: vec![account_meta],
+        data: vec![u8::MAX],
+    };
+    match data[0] {
+        2 => solana_program::program::invoke_signed(
+            &instruction,
+            accounts,
+            &[&[PDA_SEED, &[pda().1]]],
+        ),
+        _ => solana_program::program::invoke(&instruction, accounts),
+    }
+}
+
+fn readonly_accounts(pubkey: &Pubkey) -> Vec<(AccountMeta, AccountSharedData)> {
+    vec![
+        (
+            AccountMeta::new_readonly(*pubkey, false),
+            AccountSharedData::new(5, 4, &CALLER),
+        ),
+        (AccountMeta::new_readonly(CALLEE, false), program_account()),
+    ]
+}
+
+#[test]
+fn cpi_cannot_escalate_the_privileges_of_the_caller() {
+    set_stubs_v1();
+    set_program_entrypoint(&CALLEE, test_program);
+
+    // Writable escalation of a read-only account
+    let processed = process_one_instruction(&CALLER, escalate, &readonly_accounts(&ACCOUNT), &[0]);
+    assert_eq!(processed.result, Err(InstructionError::PrivilegeEscalation));
+
+    // Signer escalation of an account that did not sign
+    let processed = process_one_instruction(&CALLER, escalate, &readonly_accounts(&ACCOUNT), &[1]);
+    assert_eq!(processed.result, Err(InstructionError::PrivilegeEscalation));
+
+    // The seeds of the caller grant the signature of its PDA
+    let processed = process_one_instruction(&CALLER, escalate, &readonly_accounts(&pda().0), &[2]);
+    assert_eq!(processed.result, Ok(()));
+
+    // The same seeds sign nothing for another account
+    let processed = process_one_instruction(&CALLER, escalate, &readonly_accounts(&ACCOUNT), &[2]);
+    assert_eq!(processed.result, Err(InstructionError::PrivilegeEscalation));
+}
+
+const RECIPIENT: Pubkey = Pubkey::new_from_array([8; 32]);
+
+/// Transfers 3 lamports from the first account to the second through the System Program.
+fn transfer(
+    _program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    _data: &[u8],
+) -> ProgramResult {
+    let instruction =
+        solana_program::system_instruction::transfer(accounts[0].key, accounts[1].key, 3);
+    solana_program::program::invoke(&instruction, accounts)?;
+    assert_eq!(accounts[0].lamports(), 7);
+    assert_eq!(accounts[1].lamports(), 4);
+    Ok(())
+}
+
+#[test]
+fn cpi_into_the_system_program() {
+    set_stubs_v1();
+
+    let accounts = vec![
+        (
+            AccountMeta::new(ACCOUNT, true),
+            AccountSharedData::new(10, 0, &system_program::id()),
+        ),
+        (
+            AccountMeta::new(RECIPIENT, false),
+            AccountSharedData::new(1, 0, &system_program::id()),
+        ),
+        (
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountSharedData::default(),
+        ),
+    ];
+    let processed = process_one_instruction(&CALLER, transfer, &accounts, &[]);
+    assert_eq!(processed.result, Ok(()));
+    assert_eq!(processed.accounts[0].1.lamports(), 7);
+    assert_eq!(processed.accounts[1].1.lamports(), 4);
+}
+
+#[test]
+fn cpi_that_invents_lamports_is_unbalanced() {
+    set_stubs_v1();
+    set_program_entrypoint(&CALLEE, test_program);
+
+    let processed = process_one_instruction(
+        &CALLER,
+        test_program,
+        &accounts(AccountSharedData::new(5, 4, &CALLEE)),
+        &[INVOKE, WRITE, 1],
+    );
+    assert_eq!(processed.result, Ok(()));
+
+    let processed = process_one_instruction(
+        &CALLER,
+        test_program,
+        &accounts(AccountSharedData::new(5, 4, &CALLEE)),
+        &[INVOKE, ADD_LAMPORT],
+    );
+    assert_eq!(
+        processed.result,
+        Err(InstructionError::UnbalancedInstruction)
+    );
+}
+
+/// Assigns the first account to `CALLEE` through the System Program.
+fn assign(
+    _program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    _data: &[u8],
+) -> ProgramResult {
+    let callee = solana_program::pubkey::Pubkey::new_from_array(CALLEE.to_bytes());
+    let instruction = solana_program::system_instruction::assign(accounts[0].key, &callee);
+    solana_program::program::invoke(&instruction, accounts)?;
+    assert_eq!(*accounts[0].owner, callee);
+    Ok(())
+}
+
+#[test]
+fn cpi_assign_updates_the_owner_of_the_account_info() {
+    set_stubs_v1();
+
+    let accounts = vec![
+        (
+            AccountMeta::new(ACCOUNT, true),
+            AccountSharedData::new(10, 0, &system_program::id()),
+        ),
+        (
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountSharedData::default(),
+        ),
+    ];
+    let processed = process_one_instruction(&CALLER, assign, &accounts, &[]);
+    assert_eq!(processed.result, Ok(()));
+    assert_eq!(*processed.accounts[0].1.owner(), CALLEE);
+}
+
+const CALLER_ID: solana_program::pubkey::Pubkey =
+    solana_program::pubkey::Pubkey::new_from_array(CALLER.to_bytes());
+
+/// Has `CALLEE` shrink the first account to 2 bytes, then checks the bytes left behind.
+fn shrink(
+    _program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    _data: &[u8],
+) -> ProgramResult {
+    test_program(&CALLER_ID, accounts, &[INVOKE, REALLOC, 2])?;
+    assert_eq!(accounts[0].data_len(), 2);
+    assert_eq!(&accounts[0].try_borrow_data()?[..], &[1, 2]);
+    // The truncated bytes are still within the allocation of the serialized account
+    let data = accounts[0].try_borrow_data()?;
+    let truncated = unsafe { std::slice::from_raw_parts(data.as_ptr(), 4) };
+    assert_eq!(truncated, &[1, 2, 0, 0]);
+    Ok(())
+}
+
+#[test]
+fn cpi_shrinking_an_account_zeroes_the_truncated_data() {
+    set_stubs_v1();
+    set_program_entrypoint(&CALLEE, test_program);
+
+    let account = AccountSharedData::create(5, vec![1, 2, 3, 4], CALLEE, false, 0);
+    let processed = process_one_instruction(&CALLER, shrink, &accounts(account), &[]);
+    assert_eq!(processed.result, Ok(()));
+    assert_eq!(processed.accounts[0].1.data(), &[1, 2]);
+}
+
+/// Writes the index of every account to its first byte.
+fn write_indices(
+    _program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    _data: &[u8],
+) -> ProgramResult {
+    for (index, account) in accounts.iter().enumerate() {
+        account.try_borrow_mut_data()?[0] = index as u8;
+    }
+    Ok(())
+}
+
+/// Has `CALLEE` write to every account, with the account infos passed twice.
+fn invoke_write_indices(
+    _program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    _data: &[u8],
+) -> ProgramResult {
+    let (program, writable) = accounts.split_last().expect("program account");
+    let instruction = ProgramInstruction {
+        program_id: *program.key,
+        accounts: writable
+            .iter()
+            .map(|account| ProgramAccountMeta::new(*account.key, false))
+            .collect(),
+        data: Vec::new(),
+    };
+    let account_infos: Vec<AccountInfo> = accounts.iter().chain(accounts).cloned().collect();
+    solana_program::program::invoke(&instruction, &account_infos)?;
+    for (index, account) in writable.iter().enumerate() {
+        assert_eq!(account.try_borrow_data()?[0], index as u8);
+    }
+    Ok(())
+}
+
+#[test]
+fn cpi_with_many_accounts() {
+    set_stubs_v1();
+    set_program_entrypoint(&CALLEE, write_indices);
+
+    let mut accounts: Vec<_> = (0..63)
+        .map(|_| {
+            (
+                AccountMeta::new(Pubkey::new_unique(), false),
+                AccountSharedData::new(1, 1, &CALLEE),
+            )
+        })
+        .collect();
+    accounts.push((AccountMeta::new_readonly(CALLEE, false), program_account()));
+    let processed = process_one_instruction(&CALLER, invoke_write_indices, &accounts, &[]);
+    assert_eq!(processed.result, Ok(()));
+    for (index, (_, account)) in processed.accounts[..63].iter().enumerate() {
+        assert_eq!(account.data(), &[index as u8]);
+    }
+}
+
+/// Has `CALLEE` write `data[0]` to the first account, or leave it untouched without data.
+fn invoke_write(
+    program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let before = accounts[0].try_borrow_data()?.to_vec();
+    match data.first() {
+        Some(value) => test_program(program_id, accounts, &[INVOKE, WRITE, *value])?,
+        None => test_program(program_id, accounts, &[INVOKE, u8::MAX])?,
+    }
+    let after = accounts[0].try_borrow_data()?;
+    assert_eq!(after.len(), before.len());
+    assert_eq!(after[0], data.first().copied().unwrap_or(before[0]));
+    assert_eq!(after[1..], before[1..]);
+    Ok(())
+}
+
+#[test]
+fn cpi_propagates_only_changed_data() {
+    set_stubs_v1();
+    set_program_entrypoint(&CALLEE, test_program);
+
+    let data: Vec<u8> = (0..10_240).map(|index| index as u8).collect();
+    let account = AccountSharedData::create(5, data.clone(), CALLEE, false, 0);
+
+    let processed = process_one_instruction(&CALLER, invoke_write, &accounts(account.clone()), &[]);
+    assert_eq!(processed.result, Ok(()));
+    assert_eq!(processed.accounts[0].1.data(), &data[..]);
+
+    let processed = process_one_instruction(&CALLER, invoke_write, &accounts(account), &[7]);
+    assert_eq!(processed.result, Ok(()));
+    assert_eq!(processed.accounts[0].1.data()[0], 7);
+    assert_eq!(processed.accounts[0].1.data()[1..], data[1..]);
+}
+
+fn large_instruction_data() -> Vec<u8> {
+    (0..4_096).map(|index| index as u8).collect()
+}
+
+fn assert_large_instruction_data(
+    _program_id: &solana_program::pubkey::Pubkey,
+    _accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    assert_eq!(data, &large_instruction_data()[..]);
+    Ok(())
+}
+
+#[test]
+fn cpi_passes_the_instruction_data_to_the_callee() {
+    set_stubs_v1();
+    set_program_entrypoint(&CALLEE, assert_large_instruction_data);
+
+    let mut data = vec![INVOKE];
+    data.extend(large_instruction_data());
+    let processed = process_one_instruction(
+        &CALLER,
+        test_program,
+        &accounts(AccountSharedData::new(5, 4, &CALLEE)),
+        &data,
+    );
+    assert_eq!(processed.result, Ok(()));
+}
+
+/// Invokes an unknown program (`data[0] == 0`) or the program of the second account.
+fn invoke_program(
+    _program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let program_id = match data[0] {
+        0 => solana_program::pubkey::Pubkey::new_from_array([77; 32]),
+        _ => *accounts[1].key,
+    };
+    let instruction = ProgramInstruction {
+        program_id,
+        accounts: Vec::new(),
+        data: Vec::new(),
+    };
+    solana_program::program::invoke(&instruction, accounts)
+}
+
+#[test]
+fn cpi_into_a_program_that_is_not_loaded() {
+    set_stubs_v1();
+
+    // The program is not an account of the caller
+    let processed = process_one_instruction(
+        &CALLER,
+        invoke_program,
+        &accounts_with_program(AccountSharedData::default(), &Pubkey::new_unique()),
+        &[0],
+    );
+    assert_eq!(processed.result, Err(InstructionError::MissingAccount));
+
+    // The program is an account of the caller, but no program is loaded for it
+    let processed = process_one_instruction(
+        &CALLER,
+        invoke_program,
+        &accounts_with_program(AccountSharedData::default(), &Pubkey::new_unique()),
+        &[1],
+    );
+    assert_eq!(
+        processed.result,
+        Err(InstructionError::UnsupportedProgramId)
+    );
+}
+
+/// Invokes the program of account `data[0]` with the next depth until the depth `data[1]`.
+fn invoke_chain(
+    _program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let (depth, max_depth) = (data[0], data[1]);
+    if depth == max_depth {
+        return Ok(());
+    }
+    let instruction = ProgramInstruction {
+        program_id: *accounts[depth as usize].key,
+        accounts: accounts
+            .iter()
+            .map(|account| ProgramAccountMeta::new_readonly(*account.key, false))
+            .collect(),
+        data: vec![depth + 1, max_depth],
+    };
+    solana_program::program::invoke(&instruction, accounts)
+}
+
+#[test]
+fn cpi_deeper_than_max_cpi_depth_fails() {
+    let program_ids: Vec<Pubkey> = (0..4)
+        .map(|index| Pubkey::new_from_array([40 + index; 32]))
+        .collect();
+    for program_id in &program_ids {
+        set_program_entrypoint(program_id, invoke_chain);
+    }
+    let accounts: Vec<_> = program_ids
+        .iter()
+        .map(|program_id| {
+            (
+                AccountMeta::new_readonly(*program_id, false),
+                program_account(),
+            )
+        })
+        .collect();
+
+    set_stubs_v1_with_config(StubConfig::new().max_cpi_depth(2));
+    let processed = process_one_instruction(&CALLER, invoke_chain, &accounts, &[0, 2]);
+    assert_eq!(processed.result, Ok(()));
+    let processed = process_one_instruction(&CALLER, invoke_chain, &accounts, &[0, 3]);
+    assert_eq!(processed.result, Err(InstructionError::CallDepth));
+
+    // The runtime limit applies by default
+    set_stubs_v1_with_config(StubConfig::new());
+    let processed = process_one_instruction(&CALLER, invoke_chain, &accounts, &[0, 3]);
+    assert_eq!(processed.result, Ok(()));
+}
+
+/// Signs the CPI with a seed that is too long (`data[0] == 0`) or with too many seeds.
+fn invoke_with_invalid_seeds(
+    _program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let instruction = ProgramInstruction {
+        program_id: *accounts[1].key,
+        accounts: Vec::new(),
+        data: vec![u8::MAX],
+    };
+    let long_seed = [0; solana_program::pubkey::MAX_SEED_LEN + 1];
+    let many_seeds = vec![&[1][..]; solana_program::pubkey::MAX_SEEDS + 1];
+    let result = match data[0] {
+        0 => solana_program::program::invoke_signed(&instruction, accounts, &[&[&long_seed]]),
+        _ => solana_program::program::invoke_signed(&instruction, accounts, &[&many_seeds]),
+    };
+    assert_eq!(result, Err(ProgramError::MaxSeedLengthExceeded));
+    result
+}
+
+#[test]
+fn cpi_signed_with_invalid_seeds_fails() {
+    set_stubs_v1();
+    set_program_entrypoint(&CALLEE, test_program);
+
+    for data in [0, 1] {
+        let processed = process_one_instruction(
+            &CALLER,
+            invoke_with_invalid_seeds,
+            &accounts(AccountSharedData::new(5, 4, &CALLEE)),
+            &[data],
+        );
+        assert_eq!(
+            processed.result,
+            Err(InstructionError::MaxSeedLengthExceeded)
+        );
+    }
+}
+
+/// Has `CALLEE` truncate the first account to zero bytes.
+fn truncate(
+    _program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    _data: &[u8],
+) -> ProgramResult {
+    test_program(&CALLER_ID, accounts, &[INVOKE, REALLOC, 0])?;
+    assert_eq!(accounts[0].data_len(), 0);
+    assert!(accounts[0].try_borrow_data()?.is_empty());
+    let data = accounts[0].try_borrow_data()?;
+    let truncated = unsafe { std::slice::from_raw_parts(data.as_ptr(), 4) };
+    assert_eq!(truncated, &[0; 4]);
+    Ok(())
+}
+
+#[test]
+fn cpi_truncating_an_account_empties_the_account_info() {
+    set_stubs_v1();
+    set_program_entrypoint(&CALLEE, test_program);
+
+    let account = AccountSharedData::create(5, vec![1, 2, 3, 4], CALLEE, false, 0);
+    let processed = process_one_instruction(&CALLER, truncate, &accounts(account), &[]);
+    assert_eq!(processed.result, Ok(()));
+    assert!(processed.accounts[0].1.data().is_empty());
+}
+
+/// Makes a CPI while the data of the first account is borrowed, which `invoke` would reject.
+fn invoke_while_borrowed(
+    _program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    _data: &[u8],
+) -> ProgramResult {
+    let instruction = ProgramInstruction {
+        program_id: *accounts[1].key,
+        accounts: vec![ProgramAccountMeta::new(*accounts[0].key, false)],
+        data: vec![u8::MAX],
+    };
+    let _data = accounts[0].try_borrow_mut_data()?;
+    let result = solana_program::program::invoke_signed_unchecked(&instruction, accounts, &[]);
+    assert_eq!(result, Err(ProgramError::AccountBorrowFailed));
+    result
+}
+
+/// Makes a CPI whose callee sets return data longer than `MAX_RETURN_DATA`.
+fn invoke_setting_too_much_return_data() -> ProcessedInstruction {
+    set_program_entrypoint(&CALLEE, test_program);
+    let mut data = vec![INVOKE, SET_RETURN_DATA];
+    data.resize(2 + MAX_RETURN_DATA + 1, 0);
+    process_one_instruction(
+        &CALLER,
+        test_program,
+        &accounts(AccountSharedData::new(5, 4, &CALLEE)),
+        &data,
+    )
+}
+
+#[test]
+fn no_panic_fails_the_program_aborted_by_a_syscall() {
+    set_stubs_v1_with_config(StubConfig::new().no_panic(true));
+
+    let processed = invoke_setting_too_much_return_data();
+    assert_eq!(processed.result, Err(InstructionError::InvalidArgument));
+}
+
+#[test]
+#[should_panic(expected = "exceeds MAX_RETURN_DATA")]
+fn syscalls_aborting_the_program_panic_without_no_panic() {
+    set_stubs_v1_with_config(StubConfig::new());
+
+    invoke_setting_too_much_return_data();
+}
+
+#[test]
+fn borrow_conflicts_fail_the_cpi_without_no_panic() {
+    set_stubs_v1_with_config(StubConfig::new());
+    set_program_entrypoint(&CALLEE, test_program);
+
+    let processed = process_one_instruction(
+        &CALLER,
+        invoke_while_borrowed,
+        &accounts(AccountSharedData::new(5, 4, &CALLEE)),
+        &[],
+    );
+    assert_eq!(processed.result, Err(InstructionError::AccountBorrowFailed));
+}
+
+#[test]
+fn prepare_instruction_hook_observes_the_resolved_accounts() {
+    let observed = Rc::new(RefCell::new(Vec::new()));
+    let hook_observed = observed.clone();
+    set_stubs_v1_with_config(StubConfig::new().prepare_instruction_hook(Rc::new(
+        move |instruction, instruction_accounts, program_indices| {
+            hook_observed.borrow_mut().push((
+                instruction.program_id,
+                instruction_accounts
+                    .iter()
+                    .map(|account| (account.index_in_caller, account.is_writable))
+                    .collect::<Vec<_>>(),
+                program_indices.to_vec(),
+            ));
+        },
+    )));
+    set_program_entrypoint(&CALLEE, test_program);
+
+    let processed = process_one_instruction(
+        &CALLER,
+        test_program,
+        &accounts(AccountSharedData::new(5, 4, &CALLEE)),
+        &[INVOKE, u8::MAX],
+    );
+    assert_eq!(processed.result, Ok(()));
+    let observed = observed.borrow();
+    assert_eq!(observed.len(), 1);
+    let (program_id, instruction_accounts, program_indices) = &observed[0];
+    assert_eq!(*program_id, CALLEE);
+    // The writable account and the read-only program account of the caller
+    assert_eq!(instruction_accounts, &[(0, true), (1, false)]);
+    assert_eq!(program_indices.len(), 1);
+}
+
+/// Fills the spare room of the first account with garbage, then has `CALLEE` grow it to 12 bytes.
+fn grow(
+    _program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    _data: &[u8],
+) -> ProgramResult {
+    unsafe {
+        let data = accounts[0].try_borrow_mut_data()?.as_mut_ptr();
+        std::slice::from_raw_parts_mut(data.add(4), 16).fill(0xAA);
+    }
+    test_program(&CALLER_ID, accounts, &[INVOKE, REALLOC, 12])?;
+    assert_eq!(
+        &accounts[0].try_borrow_data()?[..],
+        &[1, 2, 3, 4, 0, 0, 0, 0, 0, 0, 0, 0]
+    );
+    Ok(())
+}
+
+#[test]
+fn cpi_growing_an_account_zeroes_the_new_bytes() {
+    set_stubs_v1();
+    set_program_entrypoint(&CALLEE, test_program);
+
+    let account = AccountSharedData::create(5, vec![1, 2, 3, 4], CALLEE, false, 0);
+    let processed = process_one_instruction(&CALLER, grow, &accounts(account), &[]);
+    assert_eq!(processed.result, Ok(()));
+    assert_eq!(
+        processed.accounts[0].1.data(),
+        &[1, 2, 3, 4, 0, 0, 0, 0, 0, 0, 0, 0]
+    );
+}
+
+/// Makes a CPI with a duplicate of the first account info, then with an account info aliasing
+/// its data.
+fn invoke_with_aliased_data(
+    _program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    _data: &[u8],
+) -> ProgramResult {
+    let instruction = ProgramInstruction {
+        program_id: *accounts[1].key,
+        accounts: vec![ProgramAccountMeta::new(*accounts[0].key, false)],
+        data: vec![u8::MAX],
+    };
+    let duplicate = accounts[0].clone();
+    solana_program::program::invoke(
+        &instruction,
+        &[accounts[0].clone(), accounts[1].clone(), duplicate],
+    )?;
+
+    let (data, data_len) = {
+        let data = accounts[0].try_borrow_mut_data()?;
+        (data.as_ptr() as *mut u8, data.len())
+    };
+    let aliased_data = unsafe { std::slice::from_raw_parts_mut(data.add(1), data_len - 1) };
+    // The account infos of a CPI share a lifetime, the fields of the aliasing one are leaked
+    let key = Box::leak(Box::new(solana_program::pubkey::Pubkey::new_from_array(
+        [7; 32],
+    )));
+    let lamports = Box::leak(Box::new(0));
+    let aliased = AccountInfo::new(
+        key,
+        false,
+        false,
+        lamports,
+        aliased_data,
+        accounts[0].owner,
+        false,
+        0,
+    );
+    let result = solana_program::program::invoke(
+        &instruction,
+        &[accounts[0].clone(), accounts[1].clone(), aliased],
+    );
+    assert_eq!(result, Err(ProgramError::InvalidArgument));
+    Ok(())
+}
+
+#[test]
+fn cpi_with_aliased_account_data_fails() {
+    set_stubs_v1();
+    set_program_entrypoint(&CALLEE, test_program);
+
+    let processed = process_one_instruction(
+        &CALLER,
+        invoke_with_aliased_data,
+        &accounts(AccountSharedData::new(5, 4, &CALLEE)),
+        &[],
+    );
+    assert_eq!(processed.result, Ok(()));
+    assert!(processed.logs.iter().any(|log| log.contains("overlaps")));
+}
+
+/// Changes the read-only first account behind the back of its `RefCell`, then makes a CPI with
+/// it: `data[0] == 0` changes its data, otherwise its lamports.
+fn modify_readonly_and_invoke(
+    _program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    match data[0] {
+        0 => unsafe { (*accounts[0].data.as_ptr())[0] = 42 },
+        _ => unsafe { **accounts[0].lamports.as_ptr() += 1 },
+    }
+    let instruction = ProgramInstruction {
+        program_id: *accounts[1].key,
+        accounts: vec![ProgramAccountMeta::new_readonly(*accounts[0].key, false)],
+        data: vec![u8::MAX],
+    };
+    solana_program::program::invoke(&instruction, accounts)
+}
+
+#[test]
+fn cpi_with_changed_readonly_accounts_fails() {
+    set_stubs_v1();
+    set_program_entrypoint(&CALLEE, test_program);
+
+    for (data, error) in [
+        (0, InstructionError::ReadonlyDataModified),
+        (1, InstructionError::ReadonlyLamportChange),
+    ] {
+        let processed = process_one_instruction(
+            &CALLER,
+            modify_readonly_and_invoke,
+            &readonly_accounts(&ACCOUNT),
+            &[data],
+        );
+        assert_eq!(processed.result, Err(error));
+    }
+}
+
+/// Resizes the first account to `data[0]` bytes without the checks of `realloc`, then makes a
+/// CPI with it.
+fn resize_and_invoke(
+    _program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    if let Some(new_len) = data.first() {
+        let new_len = *new_len as usize;
+        unsafe {
+            let data = &mut *accounts[0].data.as_ptr();
+            *data = std::slice::from_raw_parts_mut(data.as_mut_ptr(), new_len);
+            // The serialized length in front of the data, as realloc updates it
+            *(data.as_mut_ptr().offset(-8) as *mut u64) = new_len as u64;
+        }
+    }
+    let instruction = ProgramInstruction {
+        program_id: *accounts[1].key,
+        accounts: vec![ProgramAccountMeta::new(*accounts[0].key, false)],
+        data: vec![u8::MAX],
+    };
+    solana_program::program::invoke(&instruction, accounts)
+}
+
+#[test]
+fn cpi_after_a_disallowed_resize_fails() {
+    set_stubs_v1();
+    set_program_entrypoint(&CALLEE, test_program);
+
+    // The caller does not own the account and cannot resize it, whether the bytes it keeps match
+    // or not
+    for (data, result) in [
+        (&[6][..], Err(InstructionError::AccountDataSizeChanged)),
+        (&[2][..], Err(InstructionError::AccountDataSizeChanged)),
+        (&[][..], Ok(())),
+    ] {
+        let account = AccountSharedData::create(5, vec![1, 2, 3, 4], CALLEE, false, 0);
+        let processed =
+            process_one_instruction(&CALLER, resize_and_invoke, &accounts(account), data);
+        assert_eq!(processed.result, result);
+    }
+}
+
+#[test]
+fn invoke_without_seeds_does_not_sign_for_the_pda_of_the_caller() {
+    set_stubs_v1();
+    set_program_entrypoint(&CALLEE, test_program);
+
+    // A plain invoke has no signers of its own, only the signatures of the caller carry over
+    let processed = process_one_instruction(&CALLER, escalate, &readonly_accounts(&pda().0), &[1]);
+    assert_eq!(processed.result, Err(InstructionError::PrivilegeEscalation));
+
+    let processed = process_one_instruction(&CALLER, escalate, &readonly_accounts(&pda().0), &[2]);
+    assert_eq!(processed.result, Ok(()));
+}
+
+/// Has `CALLEE` write 42 to the first account through the `invoke` of the crate, while holding a
+/// borrow of its data when `data[0] == 1`.
+fn invoke_from_harness(
+    _program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let instruction = ProgramInstruction {
+        program_id: *accounts[1].key,
+        accounts: vec![ProgramAccountMeta::new(*accounts[0].key, false)],
+        data: vec![WRITE, 42],
+    };
+    if data[0] == 1 {
+        let _data = accounts[0].data.borrow();
+        return trident_syscall_stubs_v1::invoke(&instruction, accounts);
+    }
+    trident_syscall_stubs_v1::invoke(&instruction, accounts)?;
+    assert_eq!(accounts[0].data.borrow()[0], 42);
+    Ok(())
+}
+
+#[test]
+fn invoke_from_the_harness_propagates_the_changes_of_the_callee() {
+    set_stubs_v1();
+    set_program_entrypoint(&CALLEE, test_program);
+
+    let processed = process_one_instruction(
+        &CALLER,
+        invoke_from_harness,
+        &accounts(AccountSharedData::new(5, 4, &CALLEE)),
+        &[0],
+    );
+    assert_eq!(processed.result, Ok(()));
+    assert_eq!(processed.accounts[0].1.data(), &[42, 0, 0, 0]);
+
+    // Account infos which are still borrowed are rejected before the CPI
+    let processed = process_one_instruction(
+        &CALLER,
+        invoke_from_harness,
+        &accounts(AccountSharedData::new(5, 4, &CALLEE)),
+        &[1],
+    );
+    assert_eq!(processed.result, Err(InstructionError::AccountBorrowFailed));
+    assert_eq!(processed.accounts[0].1.data(), &[0, 0, 0, 0]);
+}
+
+fn fail_with_borsh_io_error(
+    _program_id: &solana_program::pubkey::Pubkey,
+    _accounts: &[AccountInfo],
+    _data: &[u8],
+) -> ProgramResult {
+    Err(ProgramError::BorshIoError(
+        "unexpected length of input".to_string(),
+    ))
+}
+
+#[test]
+fn borsh_io_errors_of_a_callee_keep_their_message() {
+    set_stubs_v1();
+    set_program_entrypoint(&CALLEE, fail_with_borsh_io_error);
+
+    let processed = process_one_instruction(
+        &CALLER,
+        test_program,
+        &accounts(AccountSharedData::new(5, 4, &CALLER)),
+        &[INVOKE],
+    );
+    assert_eq!(
+        processed.result,
+        Err(InstructionError::BorshIoError(
+            "unexpected length of input".to_string()
+        ))
+    );
+}
+
+// Writes the first byte of the instruction data to the first byte of the first account.
+solana_program_runtime::declare_process_instruction!(WriteBuiltin, 150, |invoke_context| {
+    let transaction_context = &invoke_context.transaction_context;
+    let instruction_context = transaction_context.get_current_instruction_context()?;
+    let value = instruction_context.get_instruction_data()[0];
+    let mut account = instruction_context.try_borrow_instruction_account(transaction_context, 0)?;
+    account.get_data_mut()?[0] = value;
+    Ok(())
+});
+
+#[test]
+fn cpi_into_a_registered_builtin() {
+    set_stubs_v1();
+    add_builtin_program(&CALLEE, WriteBuiltin::vm);
+
+    let processed = process_one_instruction(
+        &CALLER,
+        test_program,
+        &accounts(AccountSharedData::new(5, 4, &CALLEE)),
+        &[INVOKE, 77],
+    );
+    assert_eq!(processed.result, Ok(()));
+    assert_eq!(processed.accounts[0].1.data(), &[77, 0, 0, 0]);
+    clear_builtin_programs();
+}
+
+/// Assigns the first account to `RECIPIENT` without the System Program, then makes a CPI with it.
+fn assign_and_invoke(
+    _program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    _data: &[u8],
+) -> ProgramResult {
+    accounts[0].assign(&solana_program::pubkey::Pubkey::new_from_array(
+        RECIPIENT.to_bytes(),
+    ));
+    let instruction = ProgramInstruction {
+        program_id: *accounts[1].key,
+        accounts: vec![ProgramAccountMeta::new(*accounts[0].key, false)],
+        data: vec![u8::MAX],
+    };
+    solana_program::program::invoke(&instruction, accounts)
+}
+
+#[test]
+fn cpi_after_an_illegal_owner_change_fails() {
+    set_stubs_v1();
+    set_program_entrypoint(&CALLEE, test_program);
+
+    // The caller does not own the account
+    let processed = process_one_instruction(
+        &CALLER,
+        assign_and_invoke,
+        &accounts(AccountSharedData::new(5, 0, &CALLEE)),
+        &[],
+    );
+    assert_eq!(processed.result, Err(InstructionError::ModifiedProgramId));
+
+    // The caller owns the account, but its data is not zeroed
+    let account = AccountSharedData::create(5, vec![1, 0, 0, 0], CALLER, false, 0);
+    let processed = process_one_instruction(&CALLER, assign_and_invoke, &accounts(account), &[]);
+    assert_eq!(processed.result, Err(InstructionError::ModifiedProgramId));
+}
+
+/// Grows the first account to 60 bytes, makes a CPI with it, then resizes it to `data[0]` bytes.
+fn realloc_around_cpi(
+    _program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    accounts[0].realloc(60, false)?;
+    let instruction = ProgramInstruction {
+        program_id: *accounts[1].key,
+        accounts: vec![ProgramAccountMeta::new(*accounts[0].key, false)],
+        data: vec![u8::MAX],
+    };
+    solana_program::program::invoke(&instruction, accounts)?;
+    assert_eq!(accounts_data_growth(), 56);
+    accounts[0].realloc(data[0] as usize, false)
+}
+
+#[test]
+fn account_data_growth_is_capped_across_reallocs() {
+    set_stubs_v1_with_config(StubConfig::new().max_accounts_data_growth(100));
+    set_program_entrypoint(&CALLEE, test_program);
+
+    let processed = process_one_instruction(
+        &CALLER,
+        realloc_around_cpi,
+        &accounts(AccountSharedData::new(5, 4, &CALLER)),
+        &[100],
+    );
+    assert_eq!(processed.result, Ok(()));
+
+    // Each realloc is within the cap, both together are not
+    let processed = process_one_instruction(
+        &CALLER,
+        realloc_around_cpi,
+        &accounts(AccountSharedData::new(5, 4, &CALLER)),
+        &[120],
+    );
+    assert_eq!(
+        processed.result,
+        Err(InstructionError::MaxAccountsDataAllocationsExceeded)
+    );
+
+    // Only the limit of the runtime applies by default
+    set_stubs_v1_with_config(StubConfig::new());
+    let processed = process_one_instruction(
+        &CALLER,
+        realloc_around_cpi,
+        &accounts(AccountSharedData::new(5, 4, &CALLER)),
+        &[200],
+    );
+    assert_eq!(processed.result, Ok(()));
+}
+
+fn executable_account(owner: &Pubkey) -> AccountSharedData {
+    let mut account = AccountSharedData::new(5, 4, owner);
+    #[allow(deprecated)]
+    account.set_executable(true);
+    account
+}
+
+/// Writes 1 to the first account when `data[0] == 0`, then has the program of the second account
+/// run the rest of the data with it.
+fn write_and_invoke(
+    _program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    if data[0] == 0 {
+        accounts[0].try_borrow_mut_data()?[0] = 1;
+    }
+    let instruction = ProgramInstruction {
+        program_id: *accounts[1].key,
+        accounts: vec![ProgramAccountMeta::new(*accounts[0].key, false)],
+        data: data[1..].to_vec(),
+    };
+    solana_program::program::invoke(&instruction, accounts)
+}
+
+#[test]
+fn executable_accounts_are_protected_with_executable_checks() {
+    set_stubs_v1_with_config(StubConfig::new().executable_checks(true));
+    set_program_entrypoint(&CALLEE, test_program);
+
+    // Changed by the caller before the CPI
+    let processed = process_one_instruction(
+        &CALLER,
+        write_and_invoke,
+        &accounts(executable_account(&CALLER)),
+        &[0, u8::MAX],
+    );
+    assert_eq!(
+        processed.result,
+        Err(InstructionError::ExecutableDataModified)
+    );
+
+    // Changed by the callee
+    let processed = process_one_instruction(
+        &CALLER,
+        write_and_invoke,
+        &accounts(executable_account(&CALLEE)),
+        &[1, WRITE, 7],
+    );
+    assert_eq!(
+        processed.result,
+        Err(InstructionError::ExecutableDataModified)
+    );
+    let processed = process_one_instruction(
+        &CALLER,
+        write_and_invoke,
+        &accounts(executable_account(&CALLEE)),
+        &[1, ADD_LAMPORT],
+    );
+    assert_eq!(
+        processed.result,
+        Err(InstructionError::ExecutableLamportChange)
+    );
+
+    set_stubs_v1_with_config(StubConfig::new());
+    let processed = process_one_instruction(
+        &CALLER,
+        write_and_invoke,
+        &accounts(executable_account(&CALLER)),
+        &[0, u8::MAX],
+    );
+    assert_eq!(processed.result, Ok(()));
+}
+
+/// Has `CALLEE` write to every account, with the account infos reordered by `data[0]`.
+fn invoke_with_reordered_account_infos(
+    _program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let (program, writable) = accounts.split_last().expect("program account");
+    let instruction = ProgramInstruction {
+        program_id: *program.key,
+        accounts: writable
+            .iter()
+            .map(|account| ProgramAccountMeta::new(*account.key, false))
+            .collect(),
+        data: Vec::new(),
+    };
+    let mut account_infos = accounts.to_vec();
+    match data[0] {
+        1 => account_infos.reverse(),
+        2 => account_infos.rotate_left(2),
+        _ => {}
+    }
+    solana_program::program::invoke(&instruction, &account_infos)
+}
+
+#[test]
+fn cpi_results_do_not_depend_on_the_order_of_the_account_infos() {
+    set_stubs_v1();
+    set_program_entrypoint(&CALLEE, write_indices);
+
+    let mut accounts: Vec<_> = (20..23)
+        .map(|index| {
+            (
+                AccountMeta::new(Pubkey::new_from_array([index; 32]), false),
+                AccountSharedData::new(1, 2, &CALLEE),
+            )
+        })
+        .collect();
+    accounts.push((AccountMeta::new_readonly(CALLEE, false), program_account()));
+
+    let processed: Vec<_> = (0..3)
+        .map(|order| {
+            let processed = process_one_instruction(
+                &CALLER,
+                invoke_with_reordered_account_infos,
+                &accounts,
+                &[order],
+            );
+            assert_eq!(processed.result, Ok(()));
+            processed.accounts
+        })
+        .collect();
+    assert_eq!(processed[0], processed[1]);
+    assert_eq!(processed[0], processed[2]);
+    // The callee sees the accounts in the order of the instruction
+    assert_eq!(processed[0][1].1.data(), &[1, 0]);
+}
+
+/// Expects two accounts.
+fn require_two_accounts(
+    _program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    _data: &[u8],
+) -> ProgramResult {
+    let accounts = &mut accounts.iter();
+    solana_program::account_info::next_account_info(accounts)?;
+    solana_program::account_info::next_account_info(accounts)?;
+    Ok(())
+}
+
+/// Invokes the program of the second account with the first account, leaving the account info
+/// of the first account out when `data[0] == 0`.
+fn invoke_with_one_account(
+    _program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let instruction = ProgramInstruction {
+        program_id: *accounts[1].key,
+        accounts: vec![ProgramAccountMeta::new(*accounts[0].key, false)],
+        data: vec![],
+    };
+    match data[0] {
+        0 => solana_program::program::invoke(&instruction, &accounts[1..]),
+        _ => solana_program::program::invoke(&instruction, accounts),
+    }
+}
+
+#[test]
+fn missing_accounts_of_a_cpi_are_told_apart() {
+    set_stubs_v1();
+    set_program_entrypoint(&CALLEE, require_two_accounts);
+
+    // The account info of an instruction account is not passed
+    let processed = process_one_instruction(
+        &CALLER,
+        invoke_with_one_account,
+        &accounts(AccountSharedData::new(5, 4, &CALLEE)),
+        &[0],
+    );
+    assert_eq!(processed.result, Err(InstructionError::MissingAccount));
+    assert!(processed
+        .logs
+        .iter()
+        .any(|log| log.starts_with("Instruction references an unknown account")));
+
+    // The callee is given fewer accounts than it needs
+    let processed = process_one_instruction(
+        &CALLER,
+        invoke_with_one_account,
+        &accounts(AccountSharedData::new(5, 4, &CALLEE)),
+        &[1],
+    );
+    assert_eq!(
+        processed.result,
+        Err(InstructionError::NotEnoughAccountKeys)
+    );
+}
+
+#[test]
+fn rejected_writes_before_a_cpi_are_returned_to_the_caller() {
+    set_stubs_v1();
+    set_program_entrypoint(&CALLEE, test_program);
+
+    // The caller writes to an account of the callee, which is writable but not its own
+    let processed = process_one_instruction(
+        &CALLER,
+        write_and_invoke,
+        &accounts(AccountSharedData::new(5, 4, &CALLEE)),
+        &[0, u8::MAX],
+    );
+    assert_eq!(
+        processed.result,
+        Err(InstructionError::ExternalAccountDataModified)
+    );
+    assert_eq!(processed.accounts[0].1.data(), &[0, 0, 0, 0]);
+}
+
+#[test]
+fn only_the_owner_changes_the_data_of_an_account() {
+    set_stubs_v1();
+    set_program_entrypoint(&CALLEE, test_program);
+
+    // Without a CPI the runtime rejects the change at the end of the instruction
+    let processed = process_one_instruction(
+        &CALLER,
+        test_program,
+        &accounts(AccountSharedData::new(5, 4, &CALLEE)),
+        &[WRITE, 1],
+    );
+    assert_eq!(
+        processed.result,
+        Err(InstructionError::ExternalAccountDataModified)
+    );
+
+    // The changes of the owner are copied into the callee
+    let processed = process_one_instruction(
+        &CALLER,
+        write_and_invoke,
+        &accounts(AccountSharedData::new(5, 4, &CALLER)),
+        &[0, u8::MAX],
+    );
+    assert_eq!(processed.result, Ok(()));
+    assert_eq!(processed.accounts[0].1.data(), &[1, 0, 0, 0]);
+}
+
+#[test]
+fn cpi_enter_hook_vetoes_cpis_to_a_program() {
+    let vetoed = Pubkey::new_from_array([3; 32]);
+    let entered = Rc::new(RefCell::new(Vec::new()));
+    let observed = entered.clone();
+    set_stubs_v1_with_config(StubConfig::new().cpi_enter_hook(Rc::new(
+        move |program_id, stack_height, instruction| {
+            observed
+                .borrow_mut()
+                .push((*program_id, stack_height, instruction.data.clone()));
+            if *program_id == vetoed {
+                return Err(ProgramError::Custom(42));
+            }
+            Ok(())
+        },
+    )));
+    set_program_entrypoint(&CALLEE, test_program);
+    set_program_entrypoint(&vetoed, test_program);
+
+    let processed = process_one_instruction(
+        &CALLER,
+        test_program,
+        &accounts(AccountSharedData::new(5, 4, &CALLER)),
+        &[INVOKE, 9],
+    );
+    assert_eq!(processed.result, Ok(()));
+
+    let processed = process_one_instruction(
+        &CALLER,
+        test_program,
+        &accounts_with_program(AccountSharedData::new(5, 4, &CALLER), &vetoed),
+        &[INVOKE, 9],
+    );
+    assert_eq!(processed.result, Err(InstructionError::Custom(42)));
+    // The vetoed program is not invoked
+    assert!(!processed.logs.iter().any(|log| log.contains("invoke [2]")));
+    assert_eq!(
+        *entered.borrow(),
+        vec![(CALLEE, 2, vec![9]), (vetoed, 2, vec![9])]
+    );
+
+    // Without the hook
+    set_stubs_v1_with_config(StubConfig::new());
+    let processed = process_one_instruction(
+        &CALLER,
+        test_program,
+        &accounts_with_program(AccountSharedData::new(5, 4, &CALLER), &vetoed),
+        &[INVOKE, 9],
+    );
+    assert_eq!(processed.result, Ok(()));
+    assert_eq!(entered.borrow().len(), 2);
+}
+
+/// Grows the first account to the length in the first two bytes of the data, then invokes itself
+/// with the rest of the data if there is any.
+fn grow_and_invoke_itself(
+    program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    accounts[0].realloc(u16::from_le_bytes([data[0], data[1]]) as usize, false)?;
+    if data.len() > 2 {
+        let instruction = ProgramInstruction {
+            program_id: *program_id,
+            accounts: vec![ProgramAccountMeta::new(*accounts[0].key, false)],
+            data: data[2..].to_vec(),
+        };
+        solana_program::program::invoke(&instruction, accounts)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn callees_growing_an_account_past_the_space_of_the_caller_fail_the_cpi() {
+    set_stubs_v1();
+
+    let grow_to = |caller_len: u16, callee_len: u16| {
+        [caller_len.to_le_bytes(), callee_len.to_le_bytes()].concat()
+    };
+    // Each program may grow the account by MAX_PERMITTED_DATA_INCREASE from the length it got, the
+    // caller has space for that increase from the original 4 bytes only
+    let processed = process_one_instruction(
+        &CALLER,
+        grow_and_invoke_itself,
+        &accounts_with_program(AccountSharedData::new(5, 4, &CALLER), &CALLER),
+        &grow_to(8_000, 10_000),
+    );
+    assert_eq!(processed.result, Ok(()));
+    assert_eq!(processed.accounts[0].1.data().len(), 10_000);
+
+    let processed = process_one_instruction(
+        &CALLER,
+        grow_and_invoke_itself,
+        &accounts_with_program(AccountSharedData::new(5, 4, &CALLER), &CALLER),
+        &grow_to(8_000, 16_000),
+    );
+    assert_eq!(processed.result, Err(InstructionError::InvalidRealloc));
+}
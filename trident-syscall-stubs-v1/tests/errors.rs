@@ -0,0 +1,100 @@
+use solana_program::program_error::ProgramError;
+use solana_sdk::instruction::InstructionError;
+use solana_sdk::transaction::TransactionError;
+
+use trident_syscall_stubs_v1::*;
+
+#[test]
+fn instruction_errors_without_program_error_map_to_reserved_custom_codes() {
+    assert_eq!(
+        ProgramError::try_from_custom(InstructionError::GenericError),
+        Ok(ProgramError::Custom(UNMAPPED_INSTRUCTION_ERROR_BASE))
+    );
+    for (offset, error) in UNMAPPED_INSTRUCTION_ERRORS.iter().enumerate() {
+        assert_eq!(
+            ProgramError::try_from_custom(error.clone()),
+            Ok(ProgramError::Custom(
+                UNMAPPED_INSTRUCTION_ERROR_BASE + offset as u32
+            ))
+        );
+    }
+    assert_eq!(
+        ProgramError::try_from_custom(InstructionError::Custom(5)),
+        Ok(ProgramError::Custom(5))
+    );
+    assert_eq!(
+        ProgramError::try_from_custom(InstructionError::IllegalOwner),
+        Ok(ProgramError::IllegalOwner)
+    );
+}
+
+#[test]
+fn instruction_errors_round_trip_through_program_errors() {
+    let errors = UNMAPPED_INSTRUCTION_ERRORS.iter().cloned().chain([
+        InstructionError::Custom(7),
+        InstructionError::InvalidArgument,
+        InstructionError::IllegalOwner,
+        InstructionError::MaxAccountsDataAllocationsExceeded,
+        InstructionError::BorshIoError("invalid length".to_string()),
+    ]);
+    for error in errors {
+        let program_error = ProgramError::try_from_custom(error.clone()).unwrap();
+        assert_eq!(
+            InstructionError::try_from_custom(program_error.clone()),
+            Ok(error)
+        );
+        assert_eq!(
+            ProgramError::try_from_custom(
+                InstructionError::try_from_custom(program_error.clone()).unwrap()
+            ),
+            Ok(program_error)
+        );
+    }
+}
+
+#[test]
+fn borsh_io_errors_keep_their_message() {
+    let message = "unexpected length of input".to_string();
+    let instruction_error =
+        InstructionError::try_from_custom(ProgramError::BorshIoError(message.clone())).unwrap();
+    assert_eq!(
+        instruction_error,
+        InstructionError::BorshIoError(message.clone())
+    );
+    assert_eq!(
+        to_program_error(instruction_error),
+        ProgramError::BorshIoError(message)
+    );
+}
+
+#[test]
+fn to_program_error_never_fails() {
+    assert_eq!(
+        to_program_error(InstructionError::IllegalOwner),
+        ProgramError::IllegalOwner
+    );
+    assert_eq!(
+        to_program_error(InstructionError::CallDepth),
+        ProgramError::Custom(UNMAPPED_INSTRUCTION_ERROR_BASE + 19)
+    );
+    assert_eq!(
+        to_program_error(InstructionError::Custom(3)),
+        ProgramError::Custom(3)
+    );
+}
+
+#[test]
+fn instruction_errors_wrap_into_transaction_errors_with_their_index() {
+    assert_eq!(
+        TransactionError::try_from_custom((2, InstructionError::Custom(3))),
+        Ok(TransactionError::InstructionError(
+            2,
+            InstructionError::Custom(3)
+        ))
+    );
+    // Transactions cannot have more than u8::MAX instructions
+    assert_eq!(
+        TransactionError::try_from_custom((256, InstructionError::Custom(3))),
+        Err((256, InstructionError::Custom(3)))
+    );
+}
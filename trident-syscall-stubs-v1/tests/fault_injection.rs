@@ -0,0 +1,67 @@
+mod common;
+
+use solana_program::account_info::AccountInfo;
+use solana_program::entrypoint::ProgramResult;
+use solana_program::sysvar::Sysvar;
+use solana_sdk::account::AccountSharedData;
+use solana_sdk::instruction::InstructionError;
+use solana_sdk::program_error::UNSUPPORTED_SYSVAR;
+
+use common::*;
+use trident_syscall_stubs_v1::*;
+
+fn read_clock(
+    _program_id: &solana_program::pubkey::Pubkey,
+    _accounts: &[AccountInfo],
+    _data: &[u8],
+) -> ProgramResult {
+    solana_program::clock::Clock::get().map(|_| ())
+}
+
+#[test]
+fn injected_faults_fail_the_syscalls() {
+    set_stubs_v1_with_config(StubConfig::new().fault_injection(true));
+    set_program_entrypoint(&CALLEE, test_program);
+    let accounts = accounts(AccountSharedData::new(5, 4, &CALLEE));
+
+    inject_fault_once(
+        "sol_invoke_signed",
+        InjectedFault::InstructionError(InstructionError::Custom(9)),
+    );
+    let processed = process_one_instruction(&CALLER, test_program, &accounts, &[INVOKE, WRITE, 1]);
+    assert_eq!(processed.result, Err(InstructionError::Custom(9)));
+    // The fault was injected once only
+    let processed = process_one_instruction(&CALLER, test_program, &accounts, &[INVOKE, WRITE, 1]);
+    assert_eq!(processed.result, Ok(()));
+
+    inject_fault(
+        "sol_get_clock_sysvar",
+        InjectedFault::ReturnCode(UNSUPPORTED_SYSVAR),
+    );
+    for _ in 0..2 {
+        let processed = process_one_instruction(&CALLER, read_clock, &accounts, &[]);
+        assert_eq!(processed.result, Err(InstructionError::UnsupportedSysvar));
+    }
+    remove_fault("sol_get_clock_sysvar");
+    set_clock(&Default::default());
+    let processed = process_one_instruction(&CALLER, read_clock, &accounts, &[]);
+    assert_eq!(processed.result, Ok(()));
+    clear_faults();
+    clear_sysvar_overrides();
+    set_stubs_v1_with_config(StubConfig::new());
+}
+
+#[test]
+fn injected_faults_are_ignored_by_default() {
+    set_stubs_v1();
+    set_program_entrypoint(&CALLEE, test_program);
+    let accounts = accounts(AccountSharedData::new(5, 4, &CALLEE));
+
+    inject_fault_once(
+        "sol_invoke_signed",
+        InjectedFault::InstructionError(InstructionError::Custom(9)),
+    );
+    let processed = process_one_instruction(&CALLER, test_program, &accounts, &[INVOKE, WRITE, 1]);
+    assert_eq!(processed.result, Ok(()));
+    clear_faults();
+}
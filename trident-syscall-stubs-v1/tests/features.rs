@@ -0,0 +1,30 @@
+use std::process::Command;
+
+/// Check the crate with the given feature flags, returning whether it built and its messages.
+fn check_with_features(args: &[&str]) -> (bool, String) {
+    let output = Command::new(env!("CARGO"))
+        .arg("check")
+        .arg("--manifest-path")
+        .arg(concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml"))
+        .arg("--target-dir")
+        .arg(env!("CARGO_TARGET_TMPDIR"))
+        .args(args)
+        .output()
+        .expect("cargo runs");
+    (
+        output.status.success(),
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+    )
+}
+
+#[test]
+fn only_the_v1_stubs_can_be_selected() {
+    let (success, stderr) = check_with_features(&["--features", "v2"]);
+    assert!(!success);
+    // Reported by cargo itself, the v2 stubs are a crate of their own
+    assert!(stderr.contains("features: v2") || stderr.contains("feature: v2"));
+
+    let (success, stderr) = check_with_features(&["--no-default-features"]);
+    assert!(!success);
+    assert!(stderr.contains("the `v1` feature has to be enabled"));
+}
@@ -0,0 +1,413 @@
+mod common;
+
+use solana_program::account_info::AccountInfo;
+use solana_program::entrypoint::ProgramResult;
+use solana_program_runtime::with_mock_invoke_context;
+use solana_sdk::account::AccountSharedData;
+use solana_sdk::account::ReadableAccount;
+use solana_sdk::account::WritableAccount;
+use solana_sdk::instruction::AccountMeta;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+
+use common::*;
+use trident_syscall_stubs_v1::*;
+
+#[test]
+fn restore_resets_the_accounts_and_return_data_to_the_snapshot() {
+    let pubkey = Pubkey::new_unique();
+    let transaction_accounts = vec![(pubkey, AccountSharedData::new(5, 3, &Pubkey::default()))];
+    with_mock_invoke_context!(invoke_context, transaction_context, transaction_accounts);
+    let _guard = set_thread_context(&mut invoke_context);
+
+    let snapshot = snapshot();
+    let transaction_context = &mut get_invoke_context().transaction_context;
+    transaction_context
+        .get_account_at_index(0)
+        .unwrap()
+        .borrow_mut()
+        .data_as_mut_slice()[0] = 9;
+    transaction_context
+        .set_return_data(pubkey, vec![1])
+        .unwrap();
+
+    restore(&snapshot).unwrap();
+    let transaction_context = &get_invoke_context().transaction_context;
+    assert_eq!(
+        transaction_context
+            .get_account_at_index(0)
+            .unwrap()
+            .borrow()
+            .data(),
+        &[0, 0, 0]
+    );
+    assert!(transaction_context.get_return_data().1.is_empty());
+}
+
+fn assert_callee_is_current(
+    _program_id: &solana_program::pubkey::Pubkey,
+    _accounts: &[AccountInfo],
+    _data: &[u8],
+) -> ProgramResult {
+    assert_eq!(current_program_id(), Some(CALLEE));
+    assert_eq!(current_stack_height(), Some(2));
+    Ok(())
+}
+
+fn assert_caller_is_current(
+    program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    _data: &[u8],
+) -> ProgramResult {
+    assert_eq!(current_program_id(), Some(CALLER));
+    assert_eq!(current_stack_height(), Some(1));
+    test_program(program_id, accounts, &[INVOKE])?;
+    assert_eq!(current_program_id(), Some(CALLER));
+    assert_eq!(current_stack_height(), Some(1));
+    Ok(())
+}
+
+#[test]
+fn current_program_id_and_stack_height_follow_the_cpi() {
+    set_stubs_v1();
+    set_program_entrypoint(&CALLEE, assert_callee_is_current);
+
+    let processed = process_one_instruction(
+        &CALLER,
+        assert_caller_is_current,
+        &accounts(AccountSharedData::new(5, 4, &CALLEE)),
+        &[],
+    );
+    assert_eq!(processed.result, Ok(()));
+
+    // No instruction runs at the top level
+    with_mock_invoke_context!(invoke_context, transaction_context, vec![]);
+    let _guard = set_thread_context(&mut invoke_context);
+    assert_eq!(current_program_id(), None);
+    assert_eq!(current_stack_height(), None);
+}
+
+/// Has `CALLEE` write to the first account, then diffs the accounts against a snapshot.
+fn diff_accounts_around_cpi(
+    program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    _data: &[u8],
+) -> ProgramResult {
+    let snapshot = snapshot();
+    test_program(program_id, accounts, &[INVOKE, WRITE, 42])?;
+    assert_eq!(
+        diff_accounts(&snapshot),
+        vec![AccountDiff {
+            pubkey: ACCOUNT,
+            owner: None,
+            lamports: None,
+            data_len: None,
+            data: Some((vec![0; 4], vec![42, 0, 0, 0])),
+        }]
+    );
+    Ok(())
+}
+
+#[test]
+fn diff_accounts_lists_the_accounts_changed_by_a_cpi() {
+    set_stubs_v1();
+    set_program_entrypoint(&CALLEE, test_program);
+
+    let processed = process_one_instruction(
+        &CALLER,
+        diff_accounts_around_cpi,
+        &accounts(AccountSharedData::new(5, 4, &CALLEE)),
+        &[],
+    );
+    assert_eq!(processed.result, Ok(()));
+}
+
+#[test]
+fn pushed_instruction_contexts_nest_the_stack() {
+    let transaction_accounts = vec![
+        (CALLER, program_account()),
+        (CALLEE, program_account()),
+        (ACCOUNT, AccountSharedData::new(1, 0, &CALLER)),
+    ];
+    with_mock_invoke_context!(invoke_context, transaction_context, transaction_accounts);
+    let _guard = set_thread_context(&mut invoke_context);
+    set_stubs_v1();
+
+    assert_eq!(solana_program::instruction::get_stack_height(), 0);
+    push_instruction_context(&CALLER, &[AccountMeta::new(ACCOUNT, false)], &[]).unwrap();
+    assert_eq!(solana_program::instruction::get_stack_height(), 1);
+    assert_eq!(current_program_id(), Some(CALLER));
+    push_instruction_context(&CALLEE, &[AccountMeta::new(ACCOUNT, false)], &[]).unwrap();
+    assert_eq!(solana_program::instruction::get_stack_height(), 2);
+    assert_eq!(current_program_id(), Some(CALLEE));
+
+    pop_instruction_context().unwrap();
+    assert_eq!(solana_program::instruction::get_stack_height(), 1);
+    pop_instruction_context().unwrap();
+    assert_eq!(solana_program::instruction::get_stack_height(), 0);
+    assert!(pop_instruction_context().is_err());
+
+    // The program has to be an account of the transaction
+    assert!(push_instruction_context(&Pubkey::new_from_array([7; 32]), &[], &[]).is_err());
+}
+
+#[test]
+fn dropping_the_guard_clears_the_invoke_context() {
+    with_mock_invoke_context!(invoke_context, transaction_context, vec![]);
+    {
+        let _guard = set_thread_context(&mut invoke_context);
+        assert_eq!(current_stack_height(), None);
+    }
+    assert!(std::panic::catch_unwind(|| {
+        get_invoke_context();
+    })
+    .is_err());
+
+    // process_one_instruction restores the context it replaced, also when the program panics
+    let _guard = set_thread_context(&mut invoke_context);
+    let panicked = std::panic::catch_unwind(|| {
+        process_one_instruction(
+            &CALLER,
+            |_, _, _| panic!("program panicked"),
+            &accounts(AccountSharedData::new(5, 4, &CALLER)),
+            &[],
+        )
+    });
+    assert!(panicked.is_err());
+    assert_eq!(
+        get_invoke_context()
+            .transaction_context
+            .get_number_of_accounts(),
+        0
+    );
+}
+
+#[test]
+fn stack_height_is_0_at_the_top_level() {
+    set_stubs_v1();
+
+    // Without an invoke context
+    assert_eq!(solana_program::instruction::get_stack_height(), 0);
+
+    // With an invoke context but no instruction
+    with_mock_invoke_context!(invoke_context, transaction_context, vec![]);
+    let _guard = set_thread_context(&mut invoke_context);
+    assert_eq!(solana_program::instruction::get_stack_height(), 0);
+}
+
+/// Expects the two instructions `invoke_siblings` made before the current one.
+fn assert_siblings(
+    _program_id: &solana_program::pubkey::Pubkey,
+    _accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    if data[0] != 3 {
+        return Ok(());
+    }
+    let sibling_instructions = processed_sibling_instructions();
+    assert_eq!(sibling_instructions.len(), 2);
+    // The most recent first
+    assert_eq!(sibling_instructions[0].data, vec![2]);
+    assert_eq!(sibling_instructions[1].data, vec![1]);
+    assert_eq!(sibling_instructions[0].program_id, CALLEE);
+    assert_eq!(
+        sibling_instructions[0].accounts,
+        vec![AccountMeta::new_readonly(ACCOUNT, false)]
+    );
+    // The syscall reads the same trace
+    assert_eq!(
+        solana_program::instruction::get_processed_sibling_instruction(0)
+            .map(|instruction| instruction.data),
+        Some(vec![2])
+    );
+    Ok(())
+}
+
+/// Invokes the program of the second account three times, with 1, 2 and 3 as data.
+fn invoke_siblings(
+    _program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    _data: &[u8],
+) -> ProgramResult {
+    for data in 1..=3 {
+        let instruction = solana_program::instruction::Instruction {
+            program_id: *accounts[1].key,
+            accounts: vec![solana_program::instruction::AccountMeta::new_readonly(
+                *accounts[0].key,
+                false,
+            )],
+            data: vec![data],
+        };
+        solana_program::program::invoke(&instruction, accounts)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn processed_sibling_instructions_lists_the_previous_cpis_of_the_caller() {
+    set_stubs_v1();
+    set_program_entrypoint(&CALLEE, assert_siblings);
+
+    let processed = process_one_instruction(
+        &CALLER,
+        invoke_siblings,
+        &accounts(AccountSharedData::new(5, 4, &CALLEE)),
+        &[],
+    );
+    assert_eq!(processed.result, Ok(()));
+}
+
+#[test]
+fn seeded_generators_repeat_their_sequence() {
+    seed_rng(42);
+    let numbers: Vec<u64> = (0..8).map(|_| random_u64()).collect();
+    let mut bytes = [0; 13];
+    fill_random(&mut bytes);
+
+    seed_rng(42);
+    assert_eq!((0..8).map(|_| random_u64()).collect::<Vec<_>>(), numbers);
+    let mut repeated_bytes = [0; 13];
+    fill_random(&mut repeated_bytes);
+    assert_eq!(repeated_bytes, bytes);
+
+    seed_rng(43);
+    assert_ne!(random_u64(), numbers[0]);
+}
+
+fn assert_not_top_level(
+    _program_id: &solana_program::pubkey::Pubkey,
+    _accounts: &[AccountInfo],
+    _data: &[u8],
+) -> ProgramResult {
+    assert!(!is_top_level_instruction());
+    Ok(())
+}
+
+fn assert_top_level(
+    program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    _data: &[u8],
+) -> ProgramResult {
+    assert!(is_top_level_instruction());
+    test_program(program_id, accounts, &[INVOKE])?;
+    assert!(is_top_level_instruction());
+    Ok(())
+}
+
+#[test]
+fn is_top_level_instruction_is_only_set_outside_of_cpis() {
+    set_stubs_v1();
+    set_program_entrypoint(&CALLEE, assert_not_top_level);
+
+    let processed = process_one_instruction(
+        &CALLER,
+        assert_top_level,
+        &accounts(AccountSharedData::new(5, 4, &CALLEE)),
+        &[],
+    );
+    assert_eq!(processed.result, Ok(()));
+}
+
+/// Has `CALLEE` write 42 to the first account and reads it back from the transaction context.
+fn get_account_around_cpi(
+    program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    _data: &[u8],
+) -> ProgramResult {
+    assert_eq!(get_account(&ACCOUNT).unwrap().data(), &[0, 0, 0, 0]);
+    test_program(program_id, accounts, &[INVOKE, WRITE, 42])?;
+    let account = get_account(&ACCOUNT).unwrap();
+    assert_eq!(account.data(), &[42, 0, 0, 0]);
+    assert_eq!(account.owner(), &CALLEE);
+    // Not an account of the transaction
+    assert_eq!(get_account(&Pubkey::new_from_array([77; 32])), None);
+    Ok(())
+}
+
+#[test]
+fn get_account_reads_the_changes_of_a_cpi() {
+    set_stubs_v1();
+    set_program_entrypoint(&CALLEE, test_program);
+
+    let processed = process_one_instruction(
+        &CALLER,
+        get_account_around_cpi,
+        &accounts(AccountSharedData::new(5, 4, &CALLEE)),
+        &[],
+    );
+    assert_eq!(processed.result, Ok(()));
+}
+
+/// Sets its data as the return data, or with `data[0] == 0` dispatches instructions for `CALLEE`
+/// to the program accounts of the caller and then of the callee.
+fn dispatch_to_program_indices(
+    _program_id: &solana_program::pubkey::Pubkey,
+    _accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    if data[0] != 0 {
+        solana_program::program::set_return_data(data);
+        return Ok(());
+    }
+
+    // The accounts of the transaction are the account, the program of the callee and the
+    // program of the caller
+    let instruction = Instruction {
+        program_id: CALLEE,
+        accounts: vec![AccountMeta::new(ACCOUNT, false)],
+        data: vec![1],
+    };
+    process_instruction_with_program_indices(&instruction, &[2])?;
+    assert_eq!(get_return_data(), Some((CALLER, vec![1])));
+
+    let instruction = Instruction {
+        data: vec![2],
+        ..instruction
+    };
+    process_instruction_with_program_indices(&instruction, &[1])?;
+    assert_eq!(get_return_data(), Some((CALLEE, vec![2])));
+    Ok(())
+}
+
+#[test]
+fn process_instruction_with_program_indices_dispatches_to_the_given_program() {
+    set_stubs_v1();
+    set_program_entrypoint(&CALLEE, dispatch_to_program_indices);
+
+    let processed = process_one_instruction(
+        &CALLER,
+        dispatch_to_program_indices,
+        &accounts(AccountSharedData::new(5, 4, &CALLEE)),
+        &[0],
+    );
+    assert_eq!(processed.result, Ok(()));
+}
+
+#[test]
+fn with_invoke_context_restores_the_previous_context() {
+    set_stubs_v1();
+    with_mock_invoke_context!(outer_invoke_context, outer_transaction_context, vec![]);
+    let _guard = set_thread_context(&mut outer_invoke_context);
+    set_compute_budget(1_000);
+
+    // The macro imports what it needs, a second one needs a scope of its own
+    {
+        with_mock_invoke_context!(inner_invoke_context, inner_transaction_context, vec![]);
+        let remaining = with_invoke_context(&mut inner_invoke_context, |_| {
+            set_compute_budget(5);
+            remaining_compute_units()
+        });
+        assert_eq!(remaining, 5);
+        assert_eq!(remaining_compute_units(), 1_000);
+
+        // Also when the closure panics
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            with_invoke_context(&mut inner_invoke_context, |_| {
+                assert_eq!(remaining_compute_units(), 5);
+                panic!("the closure panicked")
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(remaining_compute_units(), 1_000);
+    }
+}
@@ -0,0 +1,295 @@
+mod common;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use solana_log_collector::ic_msg;
+use solana_program::msg;
+use solana_program_runtime::with_mock_invoke_context;
+use solana_sdk::account::AccountSharedData;
+
+use common::*;
+use trident_syscall_stubs_v1::*;
+
+#[test]
+fn take_logs_drains_the_messages_in_order() {
+    with_mock_invoke_context!(invoke_context, transaction_context, vec![]);
+    let _guard = set_thread_context(&mut invoke_context);
+    set_stubs_v1();
+
+    msg!("first");
+    msg!("second");
+    solana_program::log::sol_log_data(&[b"hi"]);
+    assert_eq!(
+        take_logs(),
+        vec![
+            "Program log: first",
+            "Program log: second",
+            "Program data: aGk="
+        ]
+    );
+    assert!(take_logs().is_empty());
+}
+
+#[test]
+fn messages_over_the_byte_limit_are_truncated_as_by_the_runtime() {
+    with_mock_invoke_context!(invoke_context, transaction_context, vec![]);
+    let _guard = set_thread_context(&mut invoke_context);
+    set_stubs_v1();
+
+    msg!("before");
+    msg!("{}", "x".repeat(20_000));
+    solana_program::log::sol_log_data(&[&[0; 20_000]]);
+    msg!("after");
+    assert_eq!(
+        take_logs(),
+        vec!["Program log: before", "Log truncated", "Program log: after"]
+    );
+}
+
+#[test]
+fn log_data_is_capped_by_the_total_size_of_its_fields() {
+    with_mock_invoke_context!(invoke_context, transaction_context, vec![]);
+    let _guard = set_thread_context(&mut invoke_context);
+    set_stubs_v1();
+
+    // Each field fits in the byte limit, both of them do not
+    let field = [7; 6_000];
+    solana_program::log::sol_log_data(&[&field, &field]);
+    msg!("after");
+    assert_eq!(take_logs(), vec!["Log truncated", "Program log: after"]);
+}
+
+#[test]
+fn custom_log_collector_receives_the_logs() {
+    with_mock_invoke_context!(invoke_context, transaction_context, vec![]);
+    let _guard = set_thread_context(&mut invoke_context);
+    set_stubs_v1();
+
+    let log_collector = LogCollector::new_ref();
+    set_log_collector(Some(log_collector.clone()));
+    msg!("custom");
+    assert_eq!(
+        log_collector.borrow().get_recorded_content(),
+        &["Program log: custom".to_string()]
+    );
+    let invoke_context_logs = get_invoke_context().get_log_collector().unwrap();
+    assert!(invoke_context_logs
+        .borrow()
+        .get_recorded_content()
+        .is_empty());
+
+    // Back to the collector of the invoke context
+    set_log_collector(None);
+    msg!("invoke context");
+    assert_eq!(take_logs(), vec!["Program log: invoke context"]);
+    assert_eq!(log_collector.borrow().get_recorded_content().len(), 1);
+}
+
+#[test]
+fn log_coverage_callback_observes_every_message() {
+    with_mock_invoke_context!(invoke_context, transaction_context, vec![]);
+    let _guard = set_thread_context(&mut invoke_context);
+    set_stubs_v1();
+
+    let hashes = Rc::new(RefCell::new(Vec::new()));
+    let observed = hashes.clone();
+    set_log_coverage_callback(Some(Box::new(move |hash| observed.borrow_mut().push(hash))));
+    msg!("a");
+    msg!("b");
+    msg!("a");
+    solana_program::log::sol_log_data(&[b"x"]);
+    set_log_coverage_callback(None);
+    msg!("c");
+
+    let hashes = hashes.borrow();
+    assert_eq!(hashes.len(), 4);
+    assert_eq!(hashes[0], hashes[2]);
+    assert_ne!(hashes[0], hashes[1]);
+    assert_ne!(hashes[0], hashes[3]);
+}
+
+#[test]
+fn pubkey_and_u64_logs_match_the_runtime() {
+    with_mock_invoke_context!(invoke_context, transaction_context, vec![]);
+    let _guard = set_thread_context(&mut invoke_context);
+    set_stubs_v1();
+
+    solana_program::pubkey::Pubkey::new_from_array([2; 32]).log();
+    solana_program::log::sol_log_64(1, 2, 3, 4, 255);
+    assert_eq!(
+        take_logs(),
+        vec![
+            "Program log: 8qbHbw2BbbTHBW1sbeqakYXVKRQM8Ne7pLK7m6CVfeR",
+            "Program log: 0x1, 0x2, 0x3, 0x4, 0xff",
+        ]
+    );
+}
+
+solana_program_runtime::declare_process_instruction!(LoggingBuiltin, 150, |invoke_context| {
+    ic_msg!(invoke_context, "builtin says hi");
+    Ok(())
+});
+
+#[test]
+fn logs_of_builtins_are_framed_by_their_invocation() {
+    set_stubs_v1();
+    add_builtin_program(&CALLEE, LoggingBuiltin::vm);
+
+    let processed = process_one_instruction(
+        &CALLER,
+        test_program,
+        &accounts(AccountSharedData::new(5, 4, &CALLEE)),
+        &[INVOKE, 77],
+    );
+    clear_builtin_programs();
+    assert_eq!(processed.result, Ok(()));
+    assert_eq!(
+        processed.logs,
+        vec![
+            format!("Program {CALLER} invoke [1]"),
+            format!("Program log: enter {CALLER} [1, 77]"),
+            format!("Program {CALLEE} invoke [2]"),
+            "builtin says hi".to_string(),
+            format!("Program {CALLEE} success"),
+            format!("Program {CALLER} success"),
+        ]
+    );
+}
+
+#[test]
+fn parse_invoke_framing_nests_the_logs_of_cpis() {
+    set_stubs_v1();
+    set_program_entrypoint(&CALLEE, test_program);
+
+    let processed = process_one_instruction(
+        &CALLER,
+        test_program,
+        &accounts(AccountSharedData::new(5, 4, &CALLEE)),
+        &[INVOKE, WRITE, 3],
+    );
+    assert_eq!(processed.result, Ok(()));
+    assert_eq!(
+        parse_invoke_framing(&processed.logs),
+        vec![InvokeFrame {
+            program_id: CALLER,
+            stack_height: 1,
+            outcome: Some(InvokeOutcome::Success),
+            logs: vec![format!("Program log: enter {CALLER} [1, 0, 3]")],
+            inner_invocations: vec![InvokeFrame {
+                program_id: CALLEE,
+                stack_height: 2,
+                outcome: Some(InvokeOutcome::Success),
+                logs: vec![format!("Program log: enter {CALLEE} [0, 3]")],
+                inner_invocations: vec![],
+            }],
+        }]
+    );
+
+    let processed = process_one_instruction(
+        &CALLER,
+        test_program,
+        &accounts(AccountSharedData::new(5, 4, &CALLEE)),
+        &[INVOKE, FAIL],
+    );
+    assert!(processed.result.is_err());
+    let frames = parse_invoke_framing(&processed.logs);
+    let callee_frame = &frames[0].inner_invocations[0];
+    assert_eq!(callee_frame.program_id, CALLEE);
+    assert_eq!(
+        callee_frame.outcome,
+        Some(InvokeOutcome::Failure(
+            "custom program error: 0x7".to_string()
+        ))
+    );
+    assert!(matches!(frames[0].outcome, Some(InvokeOutcome::Failure(_))));
+
+    // Invocations the logs do not close have no outcome
+    assert_eq!(parse_invoke_framing(&processed.logs[..2])[0].outcome, None);
+}
+
+#[test]
+fn failed_cpis_log_their_failure() {
+    set_stubs_v1();
+    set_program_entrypoint(&CALLEE, test_program);
+
+    let processed = process_one_instruction(
+        &CALLER,
+        test_program,
+        &accounts(AccountSharedData::new(5, 4, &CALLEE)),
+        &[INVOKE, FAIL],
+    );
+    assert_eq!(
+        processed.result,
+        Err(solana_sdk::instruction::InstructionError::Custom(FAILURE))
+    );
+    assert_eq!(
+        processed.logs,
+        vec![
+            format!("Program {CALLER} invoke [1]"),
+            format!("Program log: enter {CALLER} [1, 2]"),
+            format!("Program {CALLEE} invoke [2]"),
+            format!("Program log: enter {CALLEE} [2]"),
+            format!("Program {CALLEE} failed: custom program error: 0x7"),
+            format!("Program {CALLER} failed: custom program error: 0x7"),
+        ]
+    );
+}
+
+#[test]
+fn ring_buffer_keeps_the_most_recent_lines() {
+    with_mock_invoke_context!(invoke_context, transaction_context, vec![]);
+    let _guard = set_thread_context(&mut invoke_context);
+    set_stubs_v1_with_config(StubConfig::new().log_ring_buffer(3));
+    clear_buffered_logs();
+
+    for index in 0..5 {
+        msg!("{}", index);
+    }
+    solana_program::log::sol_log_data(&[b"hi"]);
+    assert_eq!(
+        buffered_logs(),
+        vec!["Program log: 3", "Program log: 4", "Program data: aGk="]
+    );
+    assert!(take_logs().is_empty());
+
+    clear_buffered_logs();
+    set_stubs_v1_with_config(StubConfig::new());
+}
+
+#[test]
+fn invoke_framing_is_not_buffered() {
+    set_stubs_v1_with_config(StubConfig::new().log_ring_buffer(10));
+    set_program_entrypoint(&CALLEE, test_program);
+    clear_buffered_logs();
+
+    let processed = process_one_instruction(
+        &CALLER,
+        test_program,
+        &accounts(AccountSharedData::new(5, 4, &CALLEE)),
+        &[INVOKE, WRITE, 3],
+    );
+    assert_eq!(processed.result, Ok(()));
+    // The framing stays in the log collector of the invoke context, the program messages are
+    // only in the ring buffer
+    assert_eq!(
+        processed.logs,
+        vec![
+            format!("Program {CALLER} invoke [1]"),
+            format!("Program {CALLEE} invoke [2]"),
+            format!("Program {CALLEE} success"),
+            format!("Program {CALLER} success"),
+        ]
+    );
+    assert_eq!(
+        buffered_logs(),
+        vec![
+            format!("Program log: enter {CALLER} [1, 0, 3]"),
+            format!("Program log: enter {CALLEE} [0, 3]"),
+        ]
+    );
+    assert!(parse_invoke_framing(&buffered_logs()).is_empty());
+
+    clear_buffered_logs();
+    set_stubs_v1_with_config(StubConfig::new());
+}
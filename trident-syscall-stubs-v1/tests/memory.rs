@@ -0,0 +1,75 @@
+mod common;
+
+use solana_program::account_info::AccountInfo;
+use solana_program::entrypoint::ProgramResult;
+use solana_sdk::account::AccountSharedData;
+
+use common::*;
+use trident_syscall_stubs_v1::*;
+
+#[test]
+fn memcmp_compares_the_bytes_as_unsigned() {
+    set_stubs_v1();
+
+    // The difference of the first differing bytes as unsigned values, as by the runtime
+    assert_eq!(
+        solana_program::program_memory::sol_memcmp(&[1, 0x00], &[1, 0xFF], 2),
+        -255
+    );
+    assert_eq!(
+        solana_program::program_memory::sol_memcmp(&[0xFF], &[0x00], 1),
+        255
+    );
+    assert_eq!(
+        solana_program::program_memory::sol_memcmp(&[0x80, 0], &[0x7F, 9], 2),
+        1
+    );
+    assert_eq!(
+        solana_program::program_memory::sol_memcmp(&[3, 4], &[3, 4], 2),
+        0
+    );
+}
+
+/// Sets `data[0]` pages of 4096 bytes from the start of the data of the first account, then
+/// copies between stack buffers.
+fn memset_pages(
+    _program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let mut account_data = accounts[0].try_borrow_mut_data()?;
+    solana_program::program_memory::sol_memset(&mut account_data, 7, data[0] as usize * 4096);
+    // Ranges outside of account data are never checked
+    let mut destination = [0; 64];
+    solana_program::program_memory::sol_memcpy(&mut destination, &[1; 64], 64);
+    Ok(())
+}
+
+#[test]
+fn strict_memory_rejects_ranges_past_the_account_data() {
+    set_stubs_v1_with_config(StubConfig::new().strict_memory(true));
+
+    // Within the data and the space it may grow into
+    let processed = process_one_instruction(
+        &CALLER,
+        memset_pages,
+        &accounts(AccountSharedData::new(5, 4, &CALLER)),
+        &[2],
+    );
+    assert_eq!(processed.result, Ok(()));
+
+    let Err(panic) = std::panic::catch_unwind(|| {
+        process_one_instruction(
+            &CALLER,
+            memset_pages,
+            &accounts(AccountSharedData::new(5, 4, &CALLER)),
+            &[3],
+        )
+    }) else {
+        panic!("the out of range memset did not fail");
+    };
+    let message = panic.downcast_ref::<String>().unwrap();
+    assert!(message.starts_with("Access violation in sol_memset"));
+
+    set_stubs_v1_with_config(StubConfig::new());
+}
@@ -0,0 +1,131 @@
+mod common;
+
+use solana_sdk::account::AccountSharedData;
+use solana_sdk::pubkey::Pubkey;
+
+use common::*;
+use trident_syscall_stubs_v1::*;
+
+#[test]
+fn syscall_counts_are_only_collected_when_enabled() {
+    set_stubs_v1();
+    set_program_entrypoint(&CALLEE, test_program);
+
+    let processed = process_one_instruction(
+        &CALLER,
+        test_program,
+        &accounts(AccountSharedData::new(5, 4, &CALLEE)),
+        &[INVOKE, WRITE, 43],
+    );
+    assert_eq!(processed.result, Ok(()));
+    assert!(syscall_counts().is_empty());
+
+    set_stubs_v1_with_config(StubConfig::new().syscall_metrics(true));
+    let processed = process_one_instruction(
+        &CALLER,
+        test_program,
+        &accounts(AccountSharedData::new(5, 4, &CALLEE)),
+        &[INVOKE, WRITE, 43],
+    );
+    assert_eq!(processed.result, Ok(()));
+    let counts = syscall_counts();
+    assert_eq!(counts.get("sol_invoke_signed"), Some(&1));
+    // The messages of the caller and the callee
+    assert_eq!(counts.get("sol_log"), Some(&2));
+
+    reset_syscall_counts();
+    assert!(syscall_counts().is_empty());
+}
+
+#[test]
+fn syscall_counts_are_per_thread() {
+    set_stubs_v1_with_config(StubConfig::new().syscall_metrics(true));
+    set_program_entrypoint(&CALLEE, test_program);
+    reset_syscall_counts();
+
+    let counts_of_other_thread = std::thread::spawn(|| {
+        set_stubs_v1_with_config(StubConfig::new().syscall_metrics(true));
+        set_program_entrypoint(&CALLEE, test_program);
+        let processed = process_one_instruction(
+            &CALLER,
+            test_program,
+            &accounts(AccountSharedData::new(5, 4, &CALLEE)),
+            &[INVOKE, WRITE, 43],
+        );
+        assert_eq!(processed.result, Ok(()));
+        syscall_counts()
+    })
+    .join()
+    .unwrap();
+    assert_eq!(counts_of_other_thread.get("sol_invoke_signed"), Some(&1));
+    assert!(syscall_counts().is_empty());
+
+    set_stubs_v1_with_config(StubConfig::new());
+}
+
+#[test]
+fn cpi_compute_units_are_recorded_per_stack_height() {
+    set_stubs_v1_with_config(StubConfig::new().cpi_compute_metrics(true));
+    set_program_entrypoint(&CALLEE, test_program);
+
+    // The caller invokes the callee, which invokes itself
+    let processed = process_one_instruction(
+        &CALLER,
+        test_program,
+        &accounts(AccountSharedData::new(5, 4, &CALLER)),
+        &[INVOKE, INVOKE, 9],
+    );
+    assert_eq!(processed.result, Ok(()));
+    let units = cpi_compute_units();
+    assert_eq!(units.keys().copied().collect::<Vec<_>>(), vec![2, 3]);
+    // The units of a CPI include those of its own CPIs
+    assert!(units[&2] > units[&3]);
+    assert!(units[&3] > 0);
+
+    reset_cpi_compute_units();
+    assert!(cpi_compute_units().is_empty());
+    set_stubs_v1_with_config(StubConfig::new());
+}
+
+#[test]
+fn cpis_are_counted_per_program_id() {
+    set_stubs_v1_with_config(StubConfig::new().syscall_metrics(true));
+    reset_cpi_counts();
+    let third = Pubkey::new_from_array([3; 32]);
+    set_program_entrypoint(&CALLEE, test_program);
+    set_program_entrypoint(&third, test_program);
+
+    for _ in 0..2 {
+        let processed = process_one_instruction(
+            &CALLER,
+            test_program,
+            &accounts(AccountSharedData::new(5, 4, &CALLER)),
+            &[INVOKE, u8::MAX],
+        );
+        assert_eq!(processed.result, Ok(()));
+    }
+    let processed = process_one_instruction(
+        &CALLER,
+        test_program,
+        &accounts_with_program(AccountSharedData::new(5, 4, &CALLER), &third),
+        &[INVOKE, u8::MAX],
+    );
+    assert_eq!(processed.result, Ok(()));
+
+    let counts = cpi_counts();
+    assert_eq!(counts.len(), 2);
+    assert_eq!(counts[&CALLEE], 2);
+    assert_eq!(counts[&third], 1);
+
+    // Only counted with the syscall metrics
+    reset_cpi_counts();
+    set_stubs_v1_with_config(StubConfig::new());
+    let processed = process_one_instruction(
+        &CALLER,
+        test_program,
+        &accounts(AccountSharedData::new(5, 4, &CALLER)),
+        &[INVOKE, u8::MAX],
+    );
+    assert_eq!(processed.result, Ok(()));
+    assert!(cpi_counts().is_empty());
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use solana_program::account_info::AccountInfo;
+use solana_program::entrypoint::ProgramResult;
+use solana_program::program_error::ProgramError;
+use solana_sdk::account::AccountSharedData;
+use solana_sdk::instruction::InstructionError;
+
+use common::*;
+use trident_syscall_stubs_v1::*;
+
+/// Fails with `Custom(FAILURE)` unless the return data is the mocked one.
+fn expect_mocked_return_data(
+    _program_id: &solana_program::pubkey::Pubkey,
+    _accounts: &[AccountInfo],
+    _data: &[u8],
+) -> ProgramResult {
+    let mocked = (
+        solana_program::pubkey::Pubkey::new_from_array(CALLEE.to_bytes()),
+        b"price=42".to_vec(),
+    );
+    if solana_program::program::get_return_data() != Some(mocked) {
+        return Err(ProgramError::Custom(FAILURE));
+    }
+    Ok(())
+}
+
+#[test]
+fn overridden_syscalls_return_the_mocked_value() {
+    set_stubs_v1_with_config(StubConfig::new().syscall_metrics(true));
+    set_syscall_override(SyscallOverride::GetReturnData(Box::new(|| {
+        Some((CALLEE, b"price=42".to_vec()))
+    })));
+
+    let processed = process_one_instruction(
+        &CALLER,
+        expect_mocked_return_data,
+        &accounts(AccountSharedData::new(5, 4, &CALLER)),
+        &[],
+    );
+    assert_eq!(processed.result, Ok(()));
+    // The override is still counted as the syscall
+    assert_eq!(syscall_counts().get("sol_get_return_data"), Some(&1));
+
+    // The overrides are registered for this thread only
+    std::thread::spawn(|| {
+        set_stubs_v1();
+        let processed = process_one_instruction(
+            &CALLER,
+            expect_mocked_return_data,
+            &accounts(AccountSharedData::new(5, 4, &CALLER)),
+            &[],
+        );
+        assert_eq!(processed.result, Err(InstructionError::Custom(FAILURE)));
+    })
+    .join()
+    .unwrap();
+
+    clear_syscall_overrides();
+}
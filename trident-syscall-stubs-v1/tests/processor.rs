@@ -0,0 +1,183 @@
+mod common;
+
+use solana_program::account_info::AccountInfo;
+use solana_program::entrypoint::ProgramResult;
+use solana_program::program_error::ProgramError;
+use solana_sdk::account::AccountSharedData;
+use solana_sdk::account::ReadableAccount;
+use solana_sdk::account::WritableAccount;
+use solana_sdk::instruction::AccountMeta;
+use solana_sdk::instruction::InstructionError;
+
+use solana_program_runtime::with_mock_invoke_context;
+
+use common::*;
+use trident_syscall_stubs_v1::*;
+
+#[test]
+fn process_one_instruction_runs_the_program_and_its_cpis() {
+    set_stubs_v1();
+    set_program_entrypoint(&CALLEE, test_program);
+
+    let processed = process_one_instruction(
+        &CALLER,
+        test_program,
+        &accounts(AccountSharedData::new(5, 4, &CALLER)),
+        &[WRITE, 42],
+    );
+    assert_eq!(processed.result, Ok(()));
+    assert_eq!(processed.accounts[0].1.data(), &[42, 0, 0, 0]);
+    assert_eq!(
+        processed.logs[1],
+        format!("Program log: enter {CALLER} [0, 42]")
+    );
+
+    let processed = process_one_instruction(
+        &CALLER,
+        test_program,
+        &accounts(AccountSharedData::new(5, 4, &CALLEE)),
+        &[INVOKE, WRITE, 43],
+    );
+    assert_eq!(processed.result, Ok(()));
+    assert_eq!(processed.accounts[0].1.data(), &[43, 0, 0, 0]);
+
+    let processed = process_one_instruction(
+        &CALLER,
+        test_program,
+        &accounts(AccountSharedData::new(5, 4, &CALLEE)),
+        &[INVOKE, FAIL],
+    );
+    assert_eq!(processed.result, Err(InstructionError::Custom(FAILURE)));
+}
+
+fn modify_readonly(
+    _program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    match data[0] {
+        0 => **accounts[0].try_borrow_mut_lamports()? -= 1,
+        1 => accounts[0].try_borrow_mut_data()?[0] = 1,
+        _ => accounts[0].assign(accounts[1].key),
+    }
+    Ok(())
+}
+
+#[test]
+fn changes_of_readonly_accounts_fail_the_instruction() {
+    set_stubs_v1();
+    let readonly_accounts = [
+        (
+            AccountMeta::new_readonly(ACCOUNT, false),
+            AccountSharedData::new(5, 4, &CALLER),
+        ),
+        (AccountMeta::new_readonly(CALLEE, false), program_account()),
+    ];
+
+    for (operation, error) in [
+        (0, InstructionError::ReadonlyLamportChange),
+        (1, InstructionError::ReadonlyDataModified),
+        (2, InstructionError::ModifiedProgramId),
+    ] {
+        let processed =
+            process_one_instruction(&CALLER, modify_readonly, &readonly_accounts, &[operation]);
+        assert_eq!(processed.result, Err(error));
+        assert_eq!(processed.accounts[0].1, readonly_accounts[0].1);
+    }
+}
+
+#[test]
+fn trace_nests_the_cpis_under_their_caller() {
+    set_stubs_v1();
+    set_program_entrypoint(&CALLEE, test_program);
+
+    // The callee invokes itself once more
+    let processed = process_one_instruction(
+        &CALLER,
+        test_program,
+        &accounts(AccountSharedData::new(5, 4, &CALLER)),
+        &[INVOKE, INVOKE, u8::MAX],
+    );
+    assert_eq!(processed.result, Ok(()));
+    assert_eq!(processed.trace.len(), 1);
+    let caller = &processed.trace[0];
+    assert_eq!(caller.program_id, CALLER);
+    assert_eq!(caller.data, vec![INVOKE, INVOKE, u8::MAX]);
+    assert_eq!(caller.inner_instructions.len(), 1);
+    let callee = &caller.inner_instructions[0];
+    assert_eq!(callee.program_id, CALLEE);
+    assert_eq!(callee.account_keys, vec![ACCOUNT, CALLEE]);
+    assert_eq!(callee.data, vec![INVOKE, u8::MAX]);
+    assert_eq!(callee.inner_instructions.len(), 1);
+    let nested = &callee.inner_instructions[0];
+    assert_eq!(nested.program_id, CALLEE);
+    assert_eq!(nested.data, vec![u8::MAX]);
+    assert!(nested.inner_instructions.is_empty());
+}
+
+#[test]
+fn assert_account_eq_reports_the_differing_fields() {
+    set_stubs_v1();
+
+    let processed = process_one_instruction(
+        &CALLER,
+        test_program,
+        &accounts(AccountSharedData::new(5, 4, &CALLER)),
+        &[WRITE, 7],
+    );
+    assert_eq!(processed.result, Ok(()));
+    let expected = AccountSharedData::create(5, vec![7, 0, 0, 0], CALLER, false, 0);
+    processed.assert_account_eq(&ACCOUNT, &expected);
+
+    let mut mismatch = expected.clone();
+    mismatch.set_lamports(6);
+    mismatch.data_as_mut_slice()[2] = 1;
+    let panic =
+        std::panic::catch_unwind(|| processed.assert_account_eq(&ACCOUNT, &mismatch)).unwrap_err();
+    let message = panic.downcast_ref::<String>().unwrap();
+    assert!(message.contains("lamports: 5 != 6"));
+    assert!(message.contains("data: first difference at byte 2, 0x00 != 0x01"));
+    assert!(!message.contains("owner"));
+
+    // The same against the accounts of the current invoke context
+    with_mock_invoke_context!(
+        invoke_context,
+        transaction_context,
+        vec![(ACCOUNT, expected.clone())]
+    );
+    let _guard = set_thread_context(&mut invoke_context);
+    assert_account_eq(&ACCOUNT, &expected);
+    assert!(std::panic::catch_unwind(|| assert_account_eq(&ACCOUNT, &mismatch)).is_err());
+}
+
+#[test]
+fn process_instructions_collecting_errors_continues_after_failures() {
+    set_stubs_v1();
+
+    let instruction = |data: Vec<u8>| InstructionInput {
+        program_id: CALLER,
+        entrypoint: test_program,
+        accounts: accounts(AccountSharedData::new(5, 4, &CALLER)),
+        data,
+    };
+    let processed = process_instructions_collecting_errors(&[
+        instruction(vec![FAIL]),
+        instruction(vec![REALLOC, 6]),
+        instruction(vec![FAIL]),
+        instruction(vec![WRITE, 7]),
+    ]);
+
+    assert_eq!(
+        processed.errors,
+        vec![
+            (0, ProgramError::Custom(FAILURE)),
+            (2, ProgramError::Custom(FAILURE))
+        ]
+    );
+    assert_eq!(processed.instructions.len(), 4);
+    // Each instruction starts from the accounts left by the previous ones
+    assert_eq!(
+        processed.instructions[3].accounts[0].1.data(),
+        &[7, 0, 0, 0, 0, 0]
+    );
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use std::path::Path;
+
+use solana_program::account_info::AccountInfo;
+use solana_program::entrypoint::ProgramResult;
+use solana_program::sysvar::Sysvar;
+use solana_sdk::account::AccountSharedData;
+
+use common::*;
+use trident_syscall_stubs_v1::*;
+
+#[test]
+fn recorder_traces_the_syscalls_in_order() {
+    set_stubs_v1();
+    set_program_entrypoint(&CALLEE, test_program);
+
+    start_recording();
+    let processed = process_one_instruction(
+        &CALLER,
+        test_program,
+        &accounts(AccountSharedData::new(5, 4, &CALLEE)),
+        &[INVOKE, SET_RETURN_DATA, 43],
+    );
+    stop_recording();
+    assert_eq!(processed.result, Ok(()));
+    assert_eq!(
+        take_syscall_events(),
+        vec![
+            SyscallEvent::Log {
+                message: format!("enter {CALLER} [1, 4, 43]"),
+            },
+            SyscallEvent::InvokeSigned { program_id: CALLEE },
+            SyscallEvent::Log {
+                message: format!("enter {CALLEE} [4, 43]"),
+            },
+            SyscallEvent::SetReturnData { len: 1 },
+        ]
+    );
+    assert!(take_syscall_events().is_empty());
+}
+
+/// Reads the clock before running `test_program`.
+fn read_clock_and_run(
+    program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let _ = solana_program::clock::Clock::get();
+    test_program(program_id, accounts, data)
+}
+
+#[test]
+fn traces_round_trip_through_files() {
+    set_stubs_v1();
+    set_program_entrypoint(&CALLEE, test_program);
+
+    start_recording();
+    let processed = process_one_instruction(
+        &CALLER,
+        read_clock_and_run,
+        &accounts(AccountSharedData::new(5, 4, &CALLEE)),
+        &[INVOKE, WRITE, 3],
+    );
+    stop_recording();
+    assert_eq!(processed.result, Ok(()));
+    let events = take_syscall_events();
+    assert!(events.contains(&SyscallEvent::InvokeSigned { program_id: CALLEE }));
+    assert!(events
+        .iter()
+        .any(|event| matches!(event, SyscallEvent::GetSysvar { .. })));
+
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("trace.json");
+    dump_trace(&path, &events).unwrap();
+    assert!(std::fs::read_to_string(&path)
+        .unwrap()
+        .starts_with("{\"version\":1,"));
+    assert_eq!(load_trace(&path).unwrap(), events);
+
+    // Traces of other versions of the format are rejected
+    std::fs::write(&path, "{\"version\":2,\"events\":[]}").unwrap();
+    assert_eq!(
+        load_trace(&path).unwrap_err().kind(),
+        std::io::ErrorKind::InvalidData
+    );
+}
@@ -0,0 +1,274 @@
+mod common;
+
+use std::rc::Rc;
+
+use solana_program::account_info::AccountInfo;
+use solana_program::entrypoint::ProgramResult;
+use solana_program::program_stubs::SyscallStubs;
+use solana_program_runtime::with_mock_invoke_context;
+use solana_sdk::account::AccountSharedData;
+use solana_sdk::instruction::InstructionError;
+
+use common::*;
+use trident_syscall_stubs_v1::*;
+
+fn caller_id() -> solana_program::pubkey::Pubkey {
+    solana_program::pubkey::Pubkey::new_from_array(CALLER.to_bytes())
+}
+
+fn callee_id() -> solana_program::pubkey::Pubkey {
+    solana_program::pubkey::Pubkey::new_from_array(CALLEE.to_bytes())
+}
+
+/// Sets its own return data, then has `CALLEE` replace it.
+fn set_return_data_around_cpi(
+    program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    _data: &[u8],
+) -> ProgramResult {
+    solana_program::program::set_return_data(&[1]);
+    assert_eq!(
+        solana_program::program::get_return_data(),
+        Some((caller_id(), vec![1]))
+    );
+    test_program(program_id, accounts, &[INVOKE, SET_RETURN_DATA, 7])?;
+    assert_eq!(
+        solana_program::program::get_return_data(),
+        Some((callee_id(), vec![7]))
+    );
+    Ok(())
+}
+
+#[test]
+fn return_data_belongs_to_the_executing_program() {
+    set_stubs_v1();
+    set_program_entrypoint(&CALLEE, test_program);
+
+    let processed = process_one_instruction(
+        &CALLER,
+        set_return_data_around_cpi,
+        &accounts(AccountSharedData::new(5, 4, &CALLEE)),
+        &[],
+    );
+    assert_eq!(processed.result, Ok(()));
+}
+
+/// Reads and clears the return data `CALLEE` sets through the helpers of the harness.
+fn clear_return_data_between_cpis(
+    _program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    _data: &[u8],
+) -> ProgramResult {
+    let instruction = solana_program::instruction::Instruction {
+        program_id: *accounts[1].key,
+        accounts: vec![],
+        data: vec![SET_RETURN_DATA, 5, 6],
+    };
+    trident_syscall_stubs_v1::invoke(&instruction, accounts)?;
+    assert_eq!(get_return_data(), Some((CALLEE, vec![5, 6])));
+    assert_eq!(
+        solana_program::program::get_return_data(),
+        Some((callee_id(), vec![5, 6]))
+    );
+
+    clear_return_data();
+    assert_eq!(get_return_data(), None);
+    assert_eq!(solana_program::program::get_return_data(), None);
+    Ok(())
+}
+
+#[test]
+fn return_data_is_read_and_cleared_by_the_harness() {
+    set_stubs_v1();
+    set_program_entrypoint(&CALLEE, test_program);
+
+    let processed = process_one_instruction(
+        &CALLER,
+        clear_return_data_between_cpis,
+        &accounts(AccountSharedData::new(5, 4, &CALLER)),
+        &[],
+    );
+    assert_eq!(processed.result, Ok(()));
+}
+
+#[test]
+fn return_data_is_not_set_outside_of_an_instruction() {
+    // Without an invoke context
+    TridentSyscallStubs.sol_set_return_data(&[1]);
+
+    // With an invoke context but no instruction
+    with_mock_invoke_context!(invoke_context, transaction_context, vec![]);
+    let _guard = set_thread_context(&mut invoke_context);
+    TridentSyscallStubs.sol_set_return_data(&[1]);
+    assert_eq!(get_return_data(), None);
+}
+
+fn assert_seeded_return_data(
+    _program_id: &solana_program::pubkey::Pubkey,
+    _accounts: &[AccountInfo],
+    _data: &[u8],
+) -> ProgramResult {
+    assert_eq!(
+        solana_program::program::get_return_data(),
+        Some((callee_id(), vec![4, 5, 6]))
+    );
+    Ok(())
+}
+
+fn assert_no_return_data(
+    _program_id: &solana_program::pubkey::Pubkey,
+    _accounts: &[AccountInfo],
+    _data: &[u8],
+) -> ProgramResult {
+    assert_eq!(solana_program::program::get_return_data(), None);
+    Ok(())
+}
+
+#[test]
+fn initial_return_data_is_seen_at_program_start() {
+    set_stubs_v1();
+    assert_eq!(
+        set_initial_return_data(&CALLEE, &[0; 1025]),
+        Err(InstructionError::InvalidArgument)
+    );
+
+    set_initial_return_data(&CALLEE, &[4, 5, 6]).unwrap();
+    let processed = process_one_instruction(
+        &CALLER,
+        assert_seeded_return_data,
+        &accounts(AccountSharedData::new(5, 4, &CALLER)),
+        &[],
+    );
+    assert_eq!(processed.result, Ok(()));
+
+    // The seeded return data is only seen by the next instruction
+    let processed = process_one_instruction(
+        &CALLER,
+        assert_no_return_data,
+        &accounts(AccountSharedData::new(5, 4, &CALLER)),
+        &[],
+    );
+    assert_eq!(processed.result, Ok(()));
+}
+
+/// Program invoked by `CALLEE`.
+const THIRD: solana_sdk::pubkey::Pubkey = solana_sdk::pubkey::Pubkey::new_from_array([3; 32]);
+
+/// Invokes the program of the first account with the remaining accounts.
+fn invoke_first(accounts: &[AccountInfo]) -> ProgramResult {
+    let instruction = solana_program::instruction::Instruction {
+        program_id: *accounts[0].key,
+        accounts: accounts[1..]
+            .iter()
+            .map(|account| {
+                solana_program::instruction::AccountMeta::new_readonly(*account.key, false)
+            })
+            .collect(),
+        data: vec![],
+    };
+    solana_program::program::invoke(&instruction, accounts)
+}
+
+/// Invokes `CALLEE` and expects its return data, not the one of `THIRD`.
+fn first_level(
+    _program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    _data: &[u8],
+) -> ProgramResult {
+    invoke_first(&accounts[1..])?;
+    assert_eq!(
+        solana_program::program::get_return_data(),
+        Some((callee_id(), b"second".to_vec()))
+    );
+    Ok(())
+}
+
+/// Invokes `THIRD` and replaces its return data.
+fn second_level(
+    _program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    _data: &[u8],
+) -> ProgramResult {
+    assert_eq!(solana_program::program::get_return_data(), None);
+    invoke_first(accounts)?;
+    assert_eq!(
+        solana_program::program::get_return_data(),
+        Some((
+            solana_program::pubkey::Pubkey::new_from_array(THIRD.to_bytes()),
+            b"third".to_vec()
+        ))
+    );
+    solana_program::program::set_return_data(b"second");
+    Ok(())
+}
+
+fn third_level(
+    _program_id: &solana_program::pubkey::Pubkey,
+    _accounts: &[AccountInfo],
+    _data: &[u8],
+) -> ProgramResult {
+    solana_program::program::set_return_data(b"third");
+    Ok(())
+}
+
+#[test]
+fn each_level_of_nested_cpis_sees_the_return_data_of_its_callee() {
+    set_stubs_v1();
+    set_program_entrypoint(&CALLEE, second_level);
+    set_program_entrypoint(&THIRD, third_level);
+
+    let mut accounts = accounts(AccountSharedData::new(5, 4, &CALLER));
+    accounts.push((
+        solana_sdk::instruction::AccountMeta::new_readonly(THIRD, false),
+        program_account(),
+    ));
+    let processed = process_one_instruction(&CALLER, first_level, &accounts, &[]);
+    assert_eq!(processed.result, Ok(()));
+}
+
+/// Has `CALLEE` set 5 and 6 as its return data and expects to read `data` instead.
+fn expect_return_data(
+    _program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let instruction = solana_program::instruction::Instruction {
+        program_id: *accounts[1].key,
+        accounts: vec![],
+        data: vec![SET_RETURN_DATA, 5, 6],
+    };
+    solana_program::program::invoke(&instruction, accounts)?;
+    assert_eq!(
+        solana_program::program::get_return_data(),
+        Some((callee_id(), data.to_vec()))
+    );
+    Ok(())
+}
+
+#[test]
+fn return_data_hook_rewrites_the_return_data() {
+    let hook: ReturnDataHook = Rc::new(|program_id, data| {
+        assert_eq!(*program_id, CALLEE);
+        data.fill(0);
+    });
+    set_stubs_v1_with_config(StubConfig::new().return_data_hook(hook));
+    set_program_entrypoint(&CALLEE, test_program);
+
+    let processed = process_one_instruction(
+        &CALLER,
+        expect_return_data,
+        &accounts(AccountSharedData::new(5, 4, &CALLER)),
+        &[0, 0],
+    );
+    assert_eq!(processed.result, Ok(()));
+
+    // Without the hook the return data is kept as is
+    set_stubs_v1_with_config(StubConfig::new());
+    let processed = process_one_instruction(
+        &CALLER,
+        expect_return_data,
+        &accounts(AccountSharedData::new(5, 4, &CALLER)),
+        &[5, 6],
+    );
+    assert_eq!(processed.result, Ok(()));
+}
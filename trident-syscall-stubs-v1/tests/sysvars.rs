@@ -0,0 +1,379 @@
+mod common;
+
+use std::sync::Arc;
+
+use solana_program::account_info::AccountInfo;
+use solana_program::entrypoint::ProgramResult;
+use solana_program::program_error::ProgramError;
+use solana_program::program_stubs::SyscallStubs;
+use solana_program::sysvar::Sysvar;
+use solana_sdk::account::AccountSharedData;
+use solana_sdk::clock::Clock;
+use solana_sdk::entrypoint::SUCCESS;
+use solana_sdk::epoch_rewards::EpochRewards;
+use solana_sdk::epoch_schedule::EpochSchedule;
+use solana_sdk::feature_set::FeatureSet;
+use solana_sdk::hash::Hash;
+use solana_sdk::instruction::InstructionError;
+use solana_sdk::program_error::INVALID_ACCOUNT_DATA;
+use solana_sdk::program_error::INVALID_ARGUMENT;
+use solana_sdk::program_error::UNSUPPORTED_SYSVAR;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::rent::Rent;
+use solana_sdk::sysvar::last_restart_slot::LastRestartSlot;
+use solana_sdk::sysvar::SysvarId;
+use solana_sdk::transaction_context::TransactionContext;
+
+use solana_compute_budget::compute_budget::ComputeBudget;
+use solana_program_runtime::invoke_context::EnvironmentConfig;
+use solana_program_runtime::loaded_programs::ProgramCacheForTxBatch;
+
+use common::*;
+use trident_syscall_stubs_v1::*;
+
+/// Run `f` with an invoke context reading its sysvars from the cache.
+fn with_sysvar_cache(sysvar_cache: &SysvarCache, f: impl FnOnce()) {
+    let mut transaction_context = TransactionContext::new(vec![], Rent::default(), 5, 64);
+    let mut program_cache = ProgramCacheForTxBatch::default();
+    let environment_config = EnvironmentConfig::new(
+        Hash::default(),
+        0,
+        0,
+        &|_| 0,
+        Arc::new(FeatureSet::all_enabled()),
+        sysvar_cache,
+    );
+    let mut invoke_context = InvokeContext::new(
+        &mut transaction_context,
+        &mut program_cache,
+        environment_config,
+        None,
+        ComputeBudget::default(),
+    );
+    with_invoke_context(&mut invoke_context, |_| {
+        set_stubs_v1();
+        f()
+    });
+}
+
+#[test]
+fn sysvar_cache_builder_seeds_the_sysvar_syscalls() {
+    let sysvar_cache = SysvarCacheBuilder::new()
+        .clock(&Clock {
+            slot: 77,
+            ..Clock::default()
+        })
+        .rent(&Rent {
+            lamports_per_byte_year: 5,
+            ..Rent::default()
+        })
+        .epoch_schedule(&EpochSchedule::custom(64, 64, false))
+        .last_restart_slot(&LastRestartSlot {
+            last_restart_slot: 12,
+        })
+        .build();
+
+    with_sysvar_cache(&sysvar_cache, || {
+        assert_eq!(solana_program::clock::Clock::get().unwrap().slot, 77);
+        assert_eq!(
+            solana_program::rent::Rent::get()
+                .unwrap()
+                .lamports_per_byte_year,
+            5
+        );
+        assert_eq!(
+            solana_program::epoch_schedule::EpochSchedule::get()
+                .unwrap()
+                .slots_per_epoch,
+            64
+        );
+        assert_eq!(
+            solana_program::last_restart_slot::LastRestartSlot::get()
+                .unwrap()
+                .last_restart_slot,
+            12
+        );
+    });
+}
+
+#[test]
+fn sysvars_missing_from_the_cache_are_unsupported() {
+    with_sysvar_cache(&SysvarCacheBuilder::new().build(), || {
+        assert_eq!(
+            solana_program::clock::Clock::get(),
+            Err(ProgramError::UnsupportedSysvar)
+        );
+    });
+}
+
+#[test]
+fn sysvars_are_only_written_through_aligned_pointers() {
+    set_clock(&Clock {
+        slot: 3,
+        ..Clock::default()
+    });
+
+    let mut clock = solana_program::clock::Clock::default();
+    let var_addr = &mut clock as *mut solana_program::clock::Clock as *mut u8;
+    assert_eq!(TridentSyscallStubs.sol_get_clock_sysvar(var_addr), SUCCESS);
+    assert_eq!(clock.slot, 3);
+
+    let mut buffer = [0u64; 6];
+    let misaligned = unsafe { (buffer.as_mut_ptr() as *mut u8).add(1) };
+    assert_eq!(
+        TridentSyscallStubs.sol_get_clock_sysvar(misaligned),
+        INVALID_ARGUMENT
+    );
+    assert_eq!(buffer, [0; 6]);
+    assert_eq!(
+        TridentSyscallStubs.sol_get_clock_sysvar(std::ptr::null_mut()),
+        INVALID_ARGUMENT
+    );
+    clear_sysvar_overrides();
+}
+
+#[test]
+fn advance_slot_moves_the_clock_forward() {
+    with_sysvar_cache(&SysvarCacheBuilder::new().build(), || {
+        advance_slot(3);
+        assert_eq!(solana_program::clock::Clock::get().unwrap().slot, 3);
+        advance_slot(4);
+        assert_eq!(solana_program::clock::Clock::get().unwrap().slot, 7);
+        clear_sysvar_overrides();
+        assert!(solana_program::clock::Clock::get().is_err());
+    });
+}
+
+#[test]
+fn epoch_rewards_are_only_returned_during_a_distribution() {
+    let epoch_rewards = EpochRewards {
+        distribution_starting_block_height: 10,
+        total_rewards: 100,
+        ..EpochRewards::default()
+    };
+
+    let sysvar_cache = SysvarCacheBuilder::new()
+        .epoch_rewards(&epoch_rewards)
+        .epoch_rewards_active(true)
+        .build();
+    with_sysvar_cache(&sysvar_cache, || {
+        let epoch_rewards = solana_program::epoch_rewards::EpochRewards::get().unwrap();
+        assert_eq!(epoch_rewards.total_rewards, 100);
+        assert_eq!(epoch_rewards.distribution_complete_block_height, 11);
+    });
+
+    let sysvar_cache = SysvarCacheBuilder::new()
+        .epoch_rewards(&epoch_rewards)
+        .epoch_rewards_active(false)
+        .build();
+    with_sysvar_cache(&sysvar_cache, || {
+        assert_eq!(
+            solana_program::epoch_rewards::EpochRewards::get(),
+            Err(ProgramError::UnsupportedSysvar)
+        );
+    });
+}
+
+#[test]
+#[allow(deprecated)]
+fn fees_are_seeded_with_the_lamports_per_signature() {
+    let sysvar_cache = SysvarCacheBuilder::new()
+        .lamports_per_signature(5_000)
+        .build();
+    with_sysvar_cache(&sysvar_cache, || {
+        let fees = solana_program::sysvar::fees::Fees::get().unwrap();
+        assert_eq!(fees.fee_calculator.lamports_per_signature, 5_000);
+    });
+}
+
+#[test]
+fn sysvar_overrides_are_read_by_the_sysvar_syscalls() {
+    set_stubs_v1();
+    set_rent(&Rent {
+        lamports_per_byte_year: 77,
+        exemption_threshold: 2.0,
+        burn_percent: 3,
+    });
+    set_epoch_schedule(&EpochSchedule::custom(1234, 1234, false));
+
+    let rent = solana_program::rent::Rent::get().unwrap();
+    assert_eq!(rent.lamports_per_byte_year, 77);
+    assert_eq!(rent.burn_percent, 3);
+    assert_eq!(
+        solana_program::epoch_schedule::EpochSchedule::get()
+            .unwrap()
+            .slots_per_epoch,
+        1234
+    );
+
+    clear_sysvar_overrides();
+    assert_eq!(
+        solana_program::rent::Rent::get(),
+        Err(ProgramError::UnsupportedSysvar)
+    );
+}
+
+#[test]
+fn warp_to_slot_moves_the_clock_into_the_epoch_of_the_slot() {
+    set_stubs_v1();
+    set_epoch_schedule(&EpochSchedule::custom(1000, 1000, false));
+    warp_to_slot(2500, Some(Hash::new_from_array([5; 32])));
+
+    let clock = solana_program::clock::Clock::get().unwrap();
+    assert_eq!(clock.slot, 2500);
+    assert_eq!(clock.epoch, 2);
+    assert_eq!(clock.leader_schedule_epoch, 3);
+
+    clear_sysvar_overrides();
+}
+
+#[test]
+fn sysvars_are_read_from_account_snapshots() {
+    let clock = Clock {
+        slot: 321,
+        epoch: 4,
+        unix_timestamp: 1_700_000_000,
+        ..Clock::default()
+    };
+    let snapshots = vec![
+        (
+            Clock::id(),
+            AccountSharedData::new_data(1, &clock, &solana_sdk::sysvar::id()).unwrap(),
+        ),
+        // Accounts which are not sysvars are ignored
+        (
+            Pubkey::new_from_array([7; 32]),
+            AccountSharedData::new(1, 3, &Pubkey::new_from_array([7; 32])),
+        ),
+    ];
+
+    set_stubs_v1();
+    set_sysvars_from_accounts(&snapshots);
+    let read_clock = solana_program::clock::Clock::get().unwrap();
+    assert_eq!(read_clock.slot, 321);
+    assert_eq!(read_clock.epoch, 4);
+    assert_eq!(read_clock.unix_timestamp, 1_700_000_000);
+    clear_sysvar_overrides();
+
+    let sysvar_cache = SysvarCacheBuilder::new().accounts(&snapshots).build();
+    with_sysvar_cache(&sysvar_cache, || {
+        assert_eq!(solana_program::clock::Clock::get().unwrap().slot, 321);
+    });
+}
+
+#[test]
+fn last_restart_slot_override_is_read_by_its_syscall() {
+    set_stubs_v1();
+    set_last_restart_slot(&LastRestartSlot {
+        last_restart_slot: 42,
+    });
+
+    let mut last_restart_slot = LastRestartSlot::default();
+    let var_addr = &mut last_restart_slot as *mut LastRestartSlot as *mut u8;
+    assert_eq!(
+        TridentSyscallStubs.sol_get_last_restart_slot(var_addr),
+        SUCCESS
+    );
+    assert_eq!(last_restart_slot.last_restart_slot, 42);
+    assert_eq!(
+        solana_program::last_restart_slot::LastRestartSlot::get()
+            .unwrap()
+            .last_restart_slot,
+        42
+    );
+
+    clear_sysvar_overrides();
+}
+
+/// Fails with `AccountNotRentExempt` unless the first account is rent exempt.
+fn require_rent_exemption(
+    _program_id: &solana_program::pubkey::Pubkey,
+    accounts: &[AccountInfo],
+    _data: &[u8],
+) -> ProgramResult {
+    let rent = solana_program::rent::Rent::get()?;
+    if !rent.is_exempt(accounts[0].lamports(), accounts[0].data_len()) {
+        return Err(ProgramError::AccountNotRentExempt);
+    }
+    Ok(())
+}
+
+#[test]
+fn rent_parameters_decide_the_rent_exemption() {
+    let rent = Rent {
+        lamports_per_byte_year: 10,
+        exemption_threshold: 1.0,
+        burn_percent: 0,
+    };
+    let sysvar_cache = SysvarCacheBuilder::new()
+        .rent_parameters(10, 1.0, 0)
+        .build();
+    assert_eq!(*sysvar_cache.get_rent().unwrap(), rent);
+
+    set_stubs_v1();
+    // The default rent without an override
+    assert_eq!(minimum_balance(4), Rent::default().minimum_balance(4));
+    set_rent(&rent);
+    let minimum_balance = minimum_balance(4);
+    assert_eq!(minimum_balance, (128 + 4) * 10);
+
+    let processed = process_one_instruction(
+        &CALLER,
+        require_rent_exemption,
+        &accounts(AccountSharedData::new(minimum_balance, 4, &CALLER)),
+        &[],
+    );
+    assert_eq!(processed.result, Ok(()));
+    let processed = process_one_instruction(
+        &CALLER,
+        require_rent_exemption,
+        &accounts(AccountSharedData::new(minimum_balance - 1, 4, &CALLER)),
+        &[],
+    );
+    assert_eq!(
+        processed.result,
+        Err(InstructionError::AccountNotRentExempt)
+    );
+
+    clear_sysvar_overrides();
+}
+
+#[test]
+fn malformed_sysvar_accounts_are_not_reported_as_missing() {
+    set_stubs_v1();
+    set_sysvars_from_accounts(&[(
+        Clock::id(),
+        AccountSharedData::new(1, 3, &solana_sdk::sysvar::id()),
+    )]);
+
+    let mut clock = Clock::default();
+    let var_addr = &mut clock as *mut Clock as *mut u8;
+    assert_eq!(
+        TridentSyscallStubs.sol_get_clock_sysvar(var_addr),
+        INVALID_ACCOUNT_DATA
+    );
+    assert_eq!(
+        solana_program::clock::Clock::get(),
+        Err(ProgramError::InvalidAccountData)
+    );
+    // Sysvars without an account are missing
+    let mut rent = Rent::default();
+    let var_addr = &mut rent as *mut Rent as *mut u8;
+    assert_eq!(
+        TridentSyscallStubs.sol_get_rent_sysvar(var_addr),
+        UNSUPPORTED_SYSVAR
+    );
+
+    // A well formed account replaces the malformed one
+    let clock = Clock {
+        slot: 9,
+        ..Clock::default()
+    };
+    set_sysvars_from_accounts(&[(
+        Clock::id(),
+        AccountSharedData::new_data(1, &clock, &solana_sdk::sysvar::id()).unwrap(),
+    )]);
+    assert_eq!(solana_program::clock::Clock::get().unwrap().slot, 9);
+
+    clear_sysvar_overrides();
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use std::sync::Arc;
+
+use solana_compute_budget::compute_budget::ComputeBudget;
+use solana_program_runtime::invoke_context::EnvironmentConfig;
+use solana_program_runtime::loaded_programs::ProgramCacheForTxBatch;
+use solana_sdk::account::AccountSharedData;
+use solana_sdk::feature_set::FeatureSet;
+use solana_sdk::hash::Hash;
+use solana_sdk::instruction::AccountMeta;
+use solana_sdk::rent::Rent;
+
+use common::*;
+use trident_syscall_stubs_v1::*;
+
+#[test]
+fn built_transaction_contexts_process_instructions() {
+    let rent = Rent {
+        lamports_per_byte_year: 7,
+        ..Rent::default()
+    };
+    let mut transaction_context = TransactionContextBuilder::new()
+        .accounts(&[(CALLER, program_account()), (CALLEE, program_account())])
+        .account(&ACCOUNT, &AccountSharedData::new(1, 0, &CALLER))
+        .rent(&rent)
+        .build();
+    assert_eq!(transaction_context.get_number_of_accounts(), 3);
+
+    let mut program_cache = ProgramCacheForTxBatch::default();
+    let sysvar_cache = SysvarCache::default();
+    let environment_config = EnvironmentConfig::new(
+        Hash::default(),
+        0,
+        0,
+        &|_| 0,
+        Arc::new(FeatureSet::all_enabled()),
+        &sysvar_cache,
+    );
+    let mut invoke_context = InvokeContext::new(
+        &mut transaction_context,
+        &mut program_cache,
+        environment_config,
+        None,
+        ComputeBudget::default(),
+    );
+    let _guard = set_thread_context(&mut invoke_context);
+    set_stubs_v1();
+
+    push_instruction_context(&CALLER, &[AccountMeta::new(ACCOUNT, false)], &[]).unwrap();
+    assert_eq!(current_program_id(), Some(CALLER));
+    assert_eq!(solana_program::instruction::get_stack_height(), 1);
+    pop_instruction_context().unwrap();
+    assert_eq!(
+        get_invoke_context()
+            .transaction_context
+            .get_instruction_trace_length(),
+        1
+    );
+}
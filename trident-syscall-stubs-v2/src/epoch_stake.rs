@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+
+use solana_sdk::pubkey::Pubkey;
+
+use solana_svm_callback::InvokeContextCallback;
+
+/// Stakes of the current epoch, returned by `sol_get_epoch_stake` once the `EnvironmentConfig`
+/// of the invoke context is created with them as its epoch stake callback.
+#[derive(Clone, Debug, Default)]
+pub struct EpochStakes {
+    total_stake: u64,
+    vote_account_stakes: HashMap<Pubkey, u64>,
+}
+
+impl InvokeContextCallback for EpochStakes {
+    fn get_epoch_stake(&self) -> u64 {
+        self.total_stake
+    }
+
+    /// Vote accounts without a stake have none, as on chain.
+    fn get_epoch_stake_for_vote_account(&self, vote_address: &Pubkey) -> u64 {
+        self.vote_account_stakes
+            .get(vote_address)
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+/// Builder of the `EpochStakes` handed over to the `EnvironmentConfig`:
+///
+/// ```ignore
+/// let epoch_stakes = EpochStakeBuilder::new()
+///     .vote_account_stake(&vote_address, 40)
+///     .build();
+/// let environment_config =
+///     EnvironmentConfig::new(blockhash, 0, &epoch_stakes, &feature_set, &sysvar_cache);
+/// ```
+#[derive(Default)]
+pub struct EpochStakeBuilder {
+    total_stake: Option<u64>,
+    vote_account_stakes: HashMap<Pubkey, u64>,
+}
+
+impl EpochStakeBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the stake delegated to the vote account, replacing the one set before.
+    pub fn vote_account_stake(mut self, vote_address: &Pubkey, stake: u64) -> Self {
+        self.vote_account_stakes.insert(*vote_address, stake);
+        self
+    }
+
+    /// Set the total stake of the epoch, returned for a null vote address. Without it the total
+    /// is the sum of the stakes of the vote accounts.
+    pub fn total_stake(mut self, total_stake: u64) -> Self {
+        self.total_stake = Some(total_stake);
+        self
+    }
+
+    pub fn build(self) -> EpochStakes {
+        let total_stake = self.total_stake.unwrap_or_else(|| {
+            self.vote_account_stakes
+                .values()
+                .fold(0, |total, stake| total.saturating_add(*stake))
+        });
+        EpochStakes {
+            total_stake,
+            vote_account_stakes: self.vote_account_stakes,
+        }
+    }
+}
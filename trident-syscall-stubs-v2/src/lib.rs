@@ -1,5 +1,7 @@
+pub mod epoch_stake;
 pub mod invoke_context;
 pub mod syscall_stubs;
 
+pub use epoch_stake::*;
 pub use invoke_context::*;
 pub use syscall_stubs::*;
@@ -11,6 +11,7 @@ use solana_sdk::entrypoint::SUCCESS;
 use solana_sdk::instruction::Instruction;
 use solana_sdk::instruction::InstructionError;
 use solana_sdk::program_error::ProgramError;
+use solana_sdk::program_error::INVALID_ARGUMENT;
 use solana_sdk::program_error::UNSUPPORTED_SYSVAR;
 use solana_sdk::program_stubs;
 use solana_sdk::program_stubs::set_syscall_stubs;
@@ -23,6 +24,10 @@ use solana_timings::ExecuteTimings;
 
 static ONCE: Once = Once::new();
 
+/// Return codes of `sol_get_sysvar`, as defined by the runtime.
+const OFFSET_LENGTH_EXCEEDS_SYSVAR: u64 = 1;
+const SYSVAR_NOT_FOUND: u64 = 2;
+
 pub fn set_stubs_v2() {
     ONCE.call_once(|| {
         set_syscall_stubs(Box::new(TridentSyscallStubs {}));
@@ -75,6 +80,65 @@ impl program_stubs::SyscallStubs for TridentSyscallStubs {
             var_addr,
         )
     }
+    // The newer syscalls are not registered by the runtime before their feature is activated,
+    // without the feature a program gets what the default stubs return when they are missing.
+    fn sol_get_sysvar(
+        &self,
+        sysvar_id_addr: *const u8,
+        var_addr: *mut u8,
+        offset: u64,
+        length: u64,
+    ) -> u64 {
+        if !get_invoke_context()
+            .get_feature_set()
+            .get_sysvar_syscall_enabled
+        {
+            return UNSUPPORTED_SYSVAR;
+        }
+        // The runtime would abort the program on an invalid address, null pointers are rejected
+        // instead of being dereferenced
+        if sysvar_id_addr.is_null() || var_addr.is_null() {
+            return INVALID_ARGUMENT;
+        }
+        let sysvar_id = unsafe { &*(sysvar_id_addr as *const Pubkey) };
+        let Some(sysvar_buf) = get_invoke_context()
+            .get_sysvar_cache()
+            .sysvar_id_to_buffer(sysvar_id)
+        else {
+            return SYSVAR_NOT_FOUND;
+        };
+        let Some(range) = usize::try_from(offset)
+            .ok()
+            .zip(usize::try_from(length).ok())
+            .and_then(|(offset, length)| Some(offset..offset.checked_add(length)?))
+            .filter(|range| range.end <= sysvar_buf.len())
+        else {
+            return OFFSET_LENGTH_EXCEEDS_SYSVAR;
+        };
+        unsafe {
+            std::ptr::copy_nonoverlapping(sysvar_buf[range].as_ptr(), var_addr, length as usize);
+        }
+        SUCCESS
+    }
+
+    /// Stake delegated to the vote account at `vote_address` in the current epoch, or the total
+    /// stake of the epoch when `vote_address` is null.
+    fn sol_get_epoch_stake(&self, vote_address: *const u8) -> u64 {
+        let invoke_context = get_invoke_context();
+        if !invoke_context
+            .get_feature_set()
+            .enable_get_epoch_stake_syscall
+        {
+            return 0;
+        }
+        if vote_address.is_null() {
+            invoke_context.get_epoch_stake()
+        } else {
+            let vote_address = unsafe { &*(vote_address as *const Pubkey) };
+            invoke_context.get_epoch_stake_for_vote_account(vote_address)
+        }
+    }
+
     fn sol_invoke_signed(
         &self,
         instruction: &Instruction,
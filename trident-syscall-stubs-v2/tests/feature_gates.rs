@@ -0,0 +1,148 @@
+use solana_sdk::account::AccountSharedData;
+use solana_sdk::clock::Clock;
+use solana_sdk::entrypoint::SUCCESS;
+use solana_sdk::hash::Hash;
+use solana_sdk::program_error::INVALID_ARGUMENT;
+use solana_sdk::program_error::UNSUPPORTED_SYSVAR;
+use solana_sdk::program_stubs::SyscallStubs;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::rent::Rent;
+use solana_sdk::sysvar::SysvarId;
+use solana_sdk::transaction_context::TransactionContext;
+
+use solana_program_runtime::execution_budget::SVMTransactionExecutionBudget;
+use solana_program_runtime::execution_budget::SVMTransactionExecutionCost;
+use solana_program_runtime::invoke_context::EnvironmentConfig;
+use solana_program_runtime::invoke_context::InvokeContext;
+use solana_program_runtime::loaded_programs::ProgramCacheForTxBatch;
+use solana_program_runtime::sysvar_cache::SysvarCache;
+use solana_svm_feature_set::SVMFeatureSet;
+
+use trident_syscall_stubs_v2::*;
+
+const VOTE_ADDRESS: Pubkey = Pubkey::new_from_array([3; 32]);
+
+/// Run `f` with an invoke context seeded with a Clock and stakes, under the given features.
+fn with_feature_set(feature_set: &SVMFeatureSet, f: impl FnOnce()) {
+    let epoch_stakes = EpochStakeBuilder::new()
+        .vote_account_stake(&VOTE_ADDRESS, 40)
+        .total_stake(100)
+        .build();
+    with_epoch_stakes(feature_set, &epoch_stakes, f);
+}
+
+/// Run `f` with an invoke context seeded with a Clock and the stakes, under the given features.
+fn with_epoch_stakes(feature_set: &SVMFeatureSet, epoch_stakes: &EpochStakes, f: impl FnOnce()) {
+    let clock = Clock {
+        slot: 7,
+        ..Clock::default()
+    };
+    let mut transaction_context = TransactionContext::new(
+        vec![(
+            Clock::id(),
+            AccountSharedData::new_data(1, &clock, &solana_sdk::sysvar::id()).unwrap(),
+        )],
+        Rent::default(),
+        5,
+        64,
+    );
+    let mut sysvar_cache = SysvarCache::default();
+    sysvar_cache.set_sysvar_for_tests(&clock);
+    let environment_config =
+        EnvironmentConfig::new(Hash::default(), 0, epoch_stakes, feature_set, &sysvar_cache);
+    let mut program_cache = ProgramCacheForTxBatch::default();
+    let mut invoke_context = InvokeContext::new(
+        &mut transaction_context,
+        &mut program_cache,
+        environment_config,
+        None,
+        SVMTransactionExecutionBudget::default(),
+        SVMTransactionExecutionCost::default(),
+    );
+    set_invoke_context(&mut invoke_context);
+    f();
+}
+
+fn get_sysvar_clock() -> u64 {
+    let mut slot = [0; 8];
+    let clock_id = Clock::id();
+    TridentSyscallStubs.sol_get_sysvar(clock_id.as_ref().as_ptr(), slot.as_mut_ptr(), 0, 8)
+}
+
+#[test]
+fn newer_syscalls_are_only_available_with_their_feature() {
+    with_feature_set(&SVMFeatureSet::all_enabled(), || {
+        assert_eq!(get_sysvar_clock(), SUCCESS);
+        assert_eq!(
+            TridentSyscallStubs.sol_get_epoch_stake(std::ptr::null()),
+            100
+        );
+        assert_eq!(
+            TridentSyscallStubs.sol_get_epoch_stake(VOTE_ADDRESS.as_ref().as_ptr()),
+            40
+        );
+    });
+
+    let mut feature_set = SVMFeatureSet::all_enabled();
+    feature_set.get_sysvar_syscall_enabled = false;
+    feature_set.enable_get_epoch_stake_syscall = false;
+    with_feature_set(&feature_set, || {
+        assert_eq!(get_sysvar_clock(), UNSUPPORTED_SYSVAR);
+        assert_eq!(TridentSyscallStubs.sol_get_epoch_stake(std::ptr::null()), 0);
+        assert_eq!(
+            TridentSyscallStubs.sol_get_epoch_stake(VOTE_ADDRESS.as_ref().as_ptr()),
+            0
+        );
+    });
+}
+
+#[test]
+fn get_sysvar_rejects_null_pointers() {
+    with_feature_set(&SVMFeatureSet::all_enabled(), || {
+        let mut slot = [0; 8];
+        let clock_id = Clock::id();
+        assert_eq!(
+            TridentSyscallStubs.sol_get_sysvar(std::ptr::null(), slot.as_mut_ptr(), 0, 8),
+            INVALID_ARGUMENT
+        );
+        assert_eq!(
+            TridentSyscallStubs.sol_get_sysvar(
+                clock_id.as_ref().as_ptr(),
+                std::ptr::null_mut(),
+                0,
+                8
+            ),
+            INVALID_ARGUMENT
+        );
+    });
+}
+
+#[test]
+fn epoch_stakes_are_read_per_vote_account() {
+    let other_vote_address = Pubkey::new_from_array([4; 32]);
+    let unknown_vote_address = Pubkey::new_from_array([5; 32]);
+    let epoch_stakes = EpochStakeBuilder::new()
+        .vote_account_stake(&VOTE_ADDRESS, 40)
+        .vote_account_stake(&other_vote_address, 60)
+        .build();
+
+    with_epoch_stakes(&SVMFeatureSet::all_enabled(), &epoch_stakes, || {
+        // Without a total stake of its own, the total is the sum of the vote accounts
+        assert_eq!(
+            TridentSyscallStubs.sol_get_epoch_stake(std::ptr::null()),
+            100
+        );
+        assert_eq!(
+            TridentSyscallStubs.sol_get_epoch_stake(VOTE_ADDRESS.as_ref().as_ptr()),
+            40
+        );
+        assert_eq!(
+            TridentSyscallStubs.sol_get_epoch_stake(other_vote_address.as_ref().as_ptr()),
+            60
+        );
+        assert_eq!(
+            TridentSyscallStubs.sol_get_epoch_stake(unknown_vote_address.as_ref().as_ptr()),
+            0
+        );
+    });
+}
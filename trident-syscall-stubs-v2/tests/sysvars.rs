@@ -0,0 +1,85 @@
+use solana_sdk::account::AccountSharedData;
+use solana_sdk::clock::Clock;
+use solana_sdk::program_stubs::SyscallStubs;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::sysvar::SysvarId;
+
+use solana_program_runtime::with_mock_invoke_context_with_feature_set;
+use solana_svm_feature_set::SVMFeatureSet;
+
+use trident_syscall_stubs_v2::*;
+
+/// Return codes of `sol_get_sysvar`.
+const OFFSET_LENGTH_EXCEEDS_SYSVAR: u64 = 1;
+const SYSVAR_NOT_FOUND: u64 = 2;
+
+#[test]
+fn get_sysvar_reads_any_sysvar_of_the_cache_by_id() {
+    let clock = Clock {
+        slot: 7,
+        ..Clock::default()
+    };
+    let transaction_accounts = vec![
+        (
+            Clock::id(),
+            AccountSharedData::new_data(1, &clock, &solana_sdk::sysvar::id()).unwrap(),
+        ),
+        (
+            Rent::id(),
+            AccountSharedData::new_data(1, &Rent::default(), &solana_sdk::sysvar::id()).unwrap(),
+        ),
+    ];
+    let feature_set = &SVMFeatureSet::all_enabled();
+    with_mock_invoke_context_with_feature_set!(
+        invoke_context,
+        transaction_context,
+        feature_set,
+        transaction_accounts
+    );
+    set_invoke_context(&mut invoke_context);
+
+    // The slot is the first field of the Clock
+    let mut slot = [0; 8];
+    let clock_id = Clock::id();
+    assert_eq!(
+        TridentSyscallStubs.sol_get_sysvar(clock_id.as_ref().as_ptr(), slot.as_mut_ptr(), 0, 8),
+        0
+    );
+    assert_eq!(u64::from_le_bytes(slot), 7);
+
+    // The lamports per byte year are the first field of the Rent
+    let mut lamports_per_byte_year = [0; 8];
+    let rent_id = Rent::id();
+    assert_eq!(
+        TridentSyscallStubs.sol_get_sysvar(
+            rent_id.as_ref().as_ptr(),
+            lamports_per_byte_year.as_mut_ptr(),
+            0,
+            8
+        ),
+        0
+    );
+    assert_eq!(
+        u64::from_le_bytes(lamports_per_byte_year),
+        Rent::default().lamports_per_byte_year
+    );
+
+    // Reads past the end of the sysvar
+    for offset in [10, u64::MAX] {
+        assert_eq!(
+            TridentSyscallStubs.sol_get_sysvar(
+                rent_id.as_ref().as_ptr(),
+                lamports_per_byte_year.as_mut_ptr(),
+                offset,
+                8
+            ),
+            OFFSET_LENGTH_EXCEEDS_SYSVAR
+        );
+    }
+
+    let unknown_id = Pubkey::new_unique();
+    assert_eq!(
+        TridentSyscallStubs.sol_get_sysvar(unknown_id.as_ref().as_ptr(), slot.as_mut_ptr(), 0, 8),
+        SYSVAR_NOT_FOUND
+    );
+}